@@ -0,0 +1,128 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiSettings};
+use bevy_persistent::Persistent;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use crate::player_camera::PlayerCamera;
+use crate::Settings;
+
+/// Colours used to outline interactables, picked so the two stay apart under common colour blindness.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter, strum_macros::Display)]
+pub enum ColourPalette {
+    #[default]
+    Standard,
+    /// For deuteranopia and protanopia, blue against orange.
+    RedGreen,
+    /// For tritanopia, red against white.
+    BlueYellow,
+    HighContrast,
+}
+
+impl ColourPalette {
+    pub fn selection(&self) -> Color {
+        match self {
+            ColourPalette::Standard => Color::GOLD,
+            ColourPalette::RedGreen => Color::rgb_u8(0, 114, 178),
+            ColourPalette::BlueYellow => Color::rgb_u8(213, 94, 0),
+            ColourPalette::HighContrast => Color::FUCHSIA,
+        }
+    }
+
+    pub fn hover(&self) -> Color {
+        match self {
+            ColourPalette::Standard => Color::ANTIQUE_WHITE,
+            ColourPalette::RedGreen => Color::rgb_u8(230, 159, 0),
+            ColourPalette::BlueYellow => Color::WHITE,
+            ColourPalette::HighContrast => Color::WHITE,
+        }
+    }
+}
+
+/// Whether the virtual pointer stays up only while `UIToggle` is held, or until it's pressed again.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter, strum_macros::Display)]
+pub enum UIToggleMode {
+    #[default]
+    Hold,
+    Toggle,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    /// Vertical field of view of the perspective camera modes, in degrees.
+    pub fov: f32,
+    /// Multiplies the size of every egui window and panel.
+    pub ui_scale: f32,
+    /// Pins the sun and moon in the top panel instead of moving them across it.
+    pub reduced_motion: bool,
+    pub palette: ColourPalette,
+    pub ui_toggle: UIToggleMode,
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        Self {
+            fov: 45.0,
+            ui_scale: 1.0,
+            reduced_motion: false,
+            palette: ColourPalette::default(),
+            ui_toggle: UIToggleMode::default(),
+        }
+    }
+}
+
+/// The accessibility page of the settings window. Returns true once anything has changed.
+pub fn accessibility_ui(ui: &mut egui::Ui, accessibility: &mut AccessibilitySettings) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Field of View");
+        changed |= ui.add(egui::Slider::new(&mut accessibility.fov, 30.0..=90.0).suffix("°")).changed();
+    });
+    ui.horizontal(|ui| {
+        ui.label("Interface Scale");
+        changed |= ui.add(egui::Slider::new(&mut accessibility.ui_scale, 0.75..=2.0).step_by(0.05)).changed();
+    });
+    changed |= ui.checkbox(&mut accessibility.reduced_motion, "Reduce Motion").changed();
+    ui.horizontal(|ui| {
+        ui.label("Highlight Colours");
+        egui::ComboBox::from_id_source("palette")
+            .selected_text(accessibility.palette.to_string())
+            .show_ui(ui, |ui| {
+                for palette in ColourPalette::iter() {
+                    changed |= ui.selectable_value(&mut accessibility.palette, palette, palette.to_string()).changed();
+                }
+            });
+    });
+    ui.horizontal(|ui| {
+        ui.label("Pointer Button");
+        for mode in UIToggleMode::iter() {
+            changed |= ui.radio_value(&mut accessibility.ui_toggle, mode, mode.to_string()).changed();
+        }
+    });
+    changed
+}
+
+/// Keeps the UI scale and camera in step with the saved settings, including when the camera is first spawned.
+fn apply_accessibility(settings: Res<Persistent<Settings>>,
+                       mut egui_settings: ResMut<EguiSettings>,
+                       mut q_camera: Query<&mut PlayerCamera>) {
+    if egui_settings.scale_factor != settings.accessibility.ui_scale {
+        egui_settings.scale_factor = settings.accessibility.ui_scale;
+    }
+    let fov = settings.accessibility.fov.to_radians();
+    for mut camera in &mut q_camera {
+        if camera.fov != fov {
+            camera.fov = fov;
+        }
+    }
+}
+
+pub struct AccessibilityPlugin;
+
+impl Plugin for AccessibilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, apply_accessibility.run_if(resource_exists::<Persistent<Settings>>));
+    }
+}
@@ -1,4 +1,97 @@
-use dracula_game::start;
-fn main() {
-    start()
-}
\ No newline at end of file
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use bevy::math::Vec2;
+
+use dracula_game::{replay_headless, start_with, LaunchOptions, PlayerMovement};
+use dracula_game::replay::InputRecording;
+use dracula_game::states::MyGameState;
+
+const USAGE: &str = "usage: dracula_game_bin [options]
+  --seed <number>                          lay the world out from this seed
+  --skip-menu <indoors|outdoors|sleeping>  go straight into the game, in this state
+  --size <width>x<height>                  window size in pixels
+  --fullscreen | --windowed                window mode, windowed by default
+  --settings <path>                        settings file to use instead of the usual one
+  --hour <0-24>                            hour of the day the clock starts at
+  --replay <recording>                     play a recording back without a window and print where it left the player";
+
+/// What the command line asked for.
+enum Launch {
+    Play(LaunchOptions),
+    Replay(PathBuf),
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Launch, String> {
+    let mut options = LaunchOptions::default();
+    let mut replay = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--seed" => options.seed = Some(value()?.parse().map_err(|error| format!("invalid seed: {error}"))?),
+            "--skip-menu" => options.skip_to = Some(match value()?.as_str() {
+                "indoors" => MyGameState::Indoors,
+                "outdoors" => MyGameState::Outdoors,
+                "sleeping" => MyGameState::Sleeping,
+                state => return Err(format!("unknown state {state}")),
+            }),
+            "--size" => {
+                let size = value()?;
+                let (width, height) = size.split_once('x').ok_or(format!("size {size} isn't <width>x<height>"))?;
+                let parse = |length: &str| length.parse::<f32>().ok().filter(|length| *length > 0.0).ok_or(format!("invalid size {size}"));
+                options.window_size = Some(Vec2::new(parse(width)?, parse(height)?));
+            }
+            "--fullscreen" => options.fullscreen = true,
+            "--windowed" => options.fullscreen = false,
+            "--settings" => options.settings_path = Some(PathBuf::from(value()?)),
+            "--hour" => {
+                let hour = value()?.parse::<f32>().map_err(|error| format!("invalid hour: {error}"))?;
+                if !(0.0..=24.0).contains(&hour) {
+                    return Err(format!("hour {hour} isn't between 0 and 24"));
+                }
+                options.start_hour = Some(hour);
+            }
+            "--replay" => replay = Some(PathBuf::from(value()?)),
+            unknown => return Err(format!("unknown option {unknown}")),
+        }
+    }
+    Ok(match replay {
+        Some(path) => Launch::Replay(path),
+        None => Launch::Play(options),
+    })
+}
+
+fn replay(path: &Path) -> ExitCode {
+    match InputRecording::<PlayerMovement>::load(path) {
+        Ok(recording) => {
+            let frames = recording.frames.len();
+            let end = replay_headless(recording);
+            println!("replayed {frames} frames, the player ended up at {} facing {}", end.translation, *end.forward());
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("can't replay {}: {error}", path.display());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    match parse_args(args.into_iter()) {
+        Ok(Launch::Play(options)) => {
+            start_with(options);
+            ExitCode::SUCCESS
+        }
+        Ok(Launch::Replay(path)) => replay(&path),
+        Err(error) => {
+            eprintln!("{error}\n{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
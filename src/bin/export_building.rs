@@ -0,0 +1,35 @@
+use std::env;
+use std::process::ExitCode;
+
+use rand::thread_rng;
+
+use dracula_game::BuildingLayout;
+use dracula_game::gltf_export::export_building;
+
+/// Generates a building without starting the game and writes it out as glTF.
+///
+/// Usage: `export_building <house|castle|floor plan name> <output.gltf|output.glb>`
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let [layout, output] = args.as_slice() else {
+        eprintln!("usage: export_building <house|castle|floor plan name> <output.gltf|output.glb>");
+        return ExitCode::FAILURE;
+    };
+    let layout = match layout.as_str() {
+        "house" => BuildingLayout::House,
+        "castle" => BuildingLayout::Castle,
+        name => BuildingLayout::FloorPlan(name.to_string()),
+    };
+    let building = match layout.generate(&mut thread_rng()) {
+        Ok(building) => building,
+        Err(error) => {
+            eprintln!("failed to lay out building: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(error) = export_building(&building, output) {
+        eprintln!("failed to export building: {error}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -3,10 +3,14 @@ use std::ops::Range;
 use std::rc::{Rc, Weak};
 
 use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::render_asset::RenderAssetUsages;
 use bevy::utils::HashMap;
 use bevy_egui::egui;
 use bevy_egui::egui::{emath, Pos2, Rangef};
-use rand::{Rng, thread_rng};
+use rand::Rng;
+
+use crate::DOOR_WIDTH;
 
 pub const HALL_WIDTH:f32 = 2.0;
 #[derive(Debug, Clone, Default,Copy)]
@@ -15,16 +19,246 @@ pub enum DoorEnum{
     Exterior,
     Interior(bool),
     Hallway,
+    /// A stairwell leading to the given storey.
+    Stairs(i32),
+}
+/// What a chunk is used for, drives how it gets spawned.
+#[derive(Debug, Clone, Default, Copy, PartialEq, Eq, Hash, strum_macros::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum RoomKind{
+    #[default]
+    Room,
+    Hallway,
+    Tower,
+    Courtyard,
+    Gallery,
+    Crypt,
+}
+/// Floor plan outline of a chunk, always inscribed in its rect.
+#[derive(Debug, Clone, Default, Copy, PartialEq, Eq, Hash, strum_macros::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum RoomShape{
+    #[default]
+    Rectangular,
+    Octagonal,
+    Circular,
+}
+pub const CIRCULAR_ROOM_SEGMENTS:usize = 16;
+/// Taller than the standing player, so a room with another built over it still leaves them room to stand.
+pub const STOREY_HEIGHT:f32 = 2.5;
+/// Doors fill their doorway, standing a little proud of the wall.
+pub const DOOR_HEIGHT:f32 = STOREY_HEIGHT + 0.1;
+pub const WALL_THICKNESS:f32 = 0.1;
+pub const MERLON_WIDTH:f32 = 0.3;
+/// A box of building geometry, sized along its local axes and placed in world space.
+#[derive(Debug,Clone,Copy)]
+pub struct BuildingBox{
+    pub transform:Transform,
+    pub size:Vec3,
 }
 #[derive(Debug,Clone)]
 pub struct BuildingChunk{
     pub rect:egui::Rect,
     pub divided_chunks:Option<BuildingChunkData>,
     pub doors:[Vec<DoorEnum>;4],
-    pub horizontal:bool
+    /// Where each door sits along its side, measured from the rect's min corner.
+    /// Doors on a side without offsets are spread evenly along it.
+    pub door_offsets:[Vec<f32>;4],
+    pub horizontal:bool,
+    pub kind:RoomKind,
+    pub shape:RoomShape,
+    /// Storey the chunk sits on, negative values are below ground.
+    pub level:i32,
+    pub battlements:bool,
+    /// Name given to authored rooms, e.g. "Harker's office".
+    pub tag:Option<String>,
+    /// Tags of the rooms whose key lies in this one.
+    pub keys:Vec<String>,
 }
 pub const MIN_ROOM_DIM:f32 = 5.0;
 impl BuildingChunk{
+    pub fn new(rect:egui::Rect,doors:[Vec<DoorEnum>;4],horizontal:bool)->Self{
+        Self{
+            rect,
+            divided_chunks:None,
+            doors,
+            door_offsets:Default::default(),
+            horizontal,
+            kind:RoomKind::default(),
+            shape:RoomShape::default(),
+            level:0,
+            battlements:false,
+            tag:None,
+            keys:Vec::new(),
+        }
+    }
+    pub fn with_kind(mut self,kind:RoomKind)->Self{
+        self.kind = kind;
+        self
+    }
+    pub fn with_shape(mut self,shape:RoomShape)->Self{
+        self.shape = shape;
+        self
+    }
+    pub fn with_level(mut self,level:i32)->Self{
+        self.level = level;
+        self
+    }
+    pub fn with_battlements(mut self,battlements:bool)->Self{
+        self.battlements = battlements;
+        self
+    }
+    pub fn with_tag(mut self,tag:Option<String>)->Self{
+        self.tag = tag;
+        self
+    }
+    pub fn with_keys(mut self,keys:Vec<String>)->Self{
+        self.keys = keys;
+        self
+    }
+    pub fn with_door_offsets(mut self,door_offsets:[Vec<f32>;4])->Self{
+        self.door_offsets = door_offsets;
+        self
+    }
+    /// Corners of the chunk outline in floor plan space, in order.
+    pub fn outline(&self)->Vec<Pos2>{
+        let rect = self.rect;
+        match self.shape{
+            RoomShape::Rectangular => vec![rect.left_top(),rect.right_top(),rect.right_bottom(),rect.left_bottom()],
+            RoomShape::Octagonal | RoomShape::Circular => {
+                let sides = if self.shape == RoomShape::Octagonal {8} else {CIRCULAR_ROOM_SEGMENTS};
+                // Offset by half a side so that a flat wall faces each door direction
+                let offset = PI / sides as f32;
+                (0..sides).map(|side|{
+                    let angle = offset + (side as f32) * 2.0 * PI / sides as f32;
+                    let radius = emath::Vec2{x:rect.width()/2.0,y:rect.height()/2.0} / (PI / sides as f32).cos();
+                    rect.center() + emath::Vec2{x:angle.cos() * radius.x,y:angle.sin() * radius.y}
+                }).collect()
+            }
+        }
+    }
+    /// Wall segments enclosing the chunk, empty for open air chunks.
+    pub fn wall_segments(&self)->Vec<(Pos2,Pos2)>{
+        if self.kind == RoomKind::Courtyard{
+            return Vec::new();
+        }
+        let outline = self.outline();
+        (0..outline.len()).map(|corner|(outline[corner],outline[(corner + 1) % outline.len()])).collect()
+    }
+    /// Height of the chunk's floor in world space.
+    pub fn floor_height(&self)->f32{
+        self.level as f32 * STOREY_HEIGHT
+    }
+    /// The first ground floor room, falling back on any other, for the player to start in.
+    pub fn spawn_room(&self)->Option<&BuildingChunk>{
+        let rooms:Vec<&BuildingChunk> = self.get_all().into_iter()
+            .filter(|chunk|!matches!(chunk.divided_chunks,Some(BuildingChunkData::Parent(..))))
+            .collect();
+        rooms.iter().find(|room|room.level == 0 && room.kind == RoomKind::Room)
+            .or_else(||rooms.iter().find(|room|room.level == 0 && room.kind != RoomKind::Courtyard))
+            .or(rooms.first())
+            .copied()
+    }
+    /// A flat floor filling the chunk outline, in world space.
+    pub fn floor_mesh(&self)->Mesh{
+        let floor = self.floor_height();
+        let outline = self.outline();
+        let positions:Vec<[f32;3]> = outline.iter().map(|corner|[corner.x,floor,corner.y]).collect();
+        let uvs:Vec<[f32;2]> = outline.iter().map(|corner|[corner.x,corner.y]).collect();
+        // Outlines wind clockwise seen from above and are always convex, so fan them out flipped to face up
+        let indices = (1..(outline.len() as u32 - 1)).flat_map(|corner|[0,corner + 1,corner]).collect();
+        Mesh::new(PrimitiveTopology::TriangleList,RenderAssetUsages::default())
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION,positions)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL,vec![[0.0,1.0,0.0];outline.len()])
+            .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0,uvs)
+            .with_inserted_indices(Indices::U32(indices))
+    }
+    /// Walls as boxes, leaving a doorway wherever one of `openings` sits on them.
+    /// Openings are door positions, as given by [`BuildingChunk::door_openings`].
+    pub fn wall_boxes(&self,openings:&[Vec3])->Vec<BuildingBox>{
+        let floor = self.floor_height();
+        let mut boxes = Vec::new();
+        for (start,end) in self.wall_segments(){
+            let start = Vec2::new(start.x,start.y);
+            let end = Vec2::new(end.x,end.y);
+            let length = start.distance(end);
+            let direction = (end - start) / length;
+            let mut doorways:Vec<(f32,f32)> = openings.iter()
+                .filter(|opening| (opening.y - (floor + STOREY_HEIGHT / 2.0)).abs() < STOREY_HEIGHT / 2.0)
+                .map(|opening| opening.xz() - start)
+                .filter(|offset| offset.perp_dot(direction).abs() < WALL_THICKNESS && (0.0..=length).contains(&offset.dot(direction)))
+                .map(|offset| (offset.dot(direction) - DOOR_WIDTH / 2.0,offset.dot(direction) + DOOR_WIDTH / 2.0))
+                .collect();
+            doorways.sort_by(|a,b| a.0.total_cmp(&b.0));
+            let mut from = 0.0;
+            for (doorway_start,doorway_end) in doorways.into_iter().chain([(length,length)]){
+                if doorway_start > from{
+                    let centre = start + direction * (from + doorway_start) / 2.0;
+                    let mut transform = Transform::from_xyz(centre.x, floor + STOREY_HEIGHT / 2.0, centre.y);
+                    transform.rotate_y(-direction.y.atan2(direction.x));
+                    boxes.push(BuildingBox{transform,size:Vec3::new(doorway_start - from,STOREY_HEIGHT,WALL_THICKNESS)});
+                }
+                from = from.max(doorway_end);
+            }
+        }
+        boxes
+    }
+    /// Positions of every door in the building that can be walked through.
+    pub fn door_openings(&self)->Vec<Vec3>{
+        self.get_all().into_iter()
+            .filter(|chunk| !matches!(chunk.divided_chunks,Some(BuildingChunkData::Parent(..))))
+            .flat_map(|chunk| chunk.door_transforms())
+            .filter(|(_,_,door,_)| !matches!(door,DoorEnum::Stairs(_)))
+            .map(|(..,transform)| transform.translation)
+            .collect()
+    }
+    /// Crenellations along the top of the walls, leaving a gap the width of a merlon between each pair.
+    pub fn merlon_boxes(&self)->Vec<BuildingBox>{
+        if !self.battlements{
+            return Vec::new();
+        }
+        let mut merlons = Vec::new();
+        for wall in self.wall_boxes(&[]){
+            let count = (wall.size.x / (2.0 * MERLON_WIDTH)) as usize;
+            for merlon in 0..count{
+                let along = (merlon as f32 * 2.0 + 0.5) * MERLON_WIDTH - wall.size.x / 2.0;
+                let mut transform = wall.transform;
+                transform.translation += wall.transform.right() * along + Vec3::Y * (STOREY_HEIGHT + MERLON_WIDTH) / 2.0;
+                merlons.push(BuildingBox{transform,size:Vec3::new(MERLON_WIDTH,MERLON_WIDTH,WALL_THICKNESS * 2.0)});
+            }
+        }
+        merlons
+    }
+    /// Where each door sits, with the side and index it has in `doors`.
+    pub fn door_transforms(&self)->Vec<(usize,usize,DoorEnum,Transform)>{
+        let room = self.rect;
+        let floor = self.floor_height();
+        let mut transforms = Vec::new();
+        for dir in 0..self.doors.len() {
+            let angle = dir as f32 * PI / 2.0;
+            let angle_vec = Vec2::from_angle(angle);
+            let corner_pos = room.center() + emath::Vec2 { x: (angle_vec.x * room.width() / 2.0), y: (angle_vec.y * room.height() / 2.0) };
+            let offsets = &self.door_offsets[dir];
+            for door_num in 0..self.doors[dir].len() {
+                // Odd sides run along x, checked on `dir` since sin(PI) isn't quite zero
+                let along_x = dir % 2 == 1;
+                let side = if along_x {room.width()} else {room.height()};
+                let offset = if offsets.len() == self.doors[dir].len() {
+                    offsets[door_num]
+                } else {
+                    (side / (self.doors[dir].len()) as f32) * ((door_num) as f32 + 0.5)
+                };
+                let mut door_transform = if along_x {
+                    Transform::from_xyz(room.min.x + offset, floor + STOREY_HEIGHT / 2.0, corner_pos.y)
+                } else {
+                    Transform::from_xyz(corner_pos.x, floor + STOREY_HEIGHT / 2.0, room.min.y + offset)
+                };
+                door_transform.rotate_local_y(angle);
+                transforms.push((dir,door_num,self.doors[dir][door_num],door_transform));
+            }
+        }
+        transforms
+    }
     pub fn get_all(&self)->Vec<&BuildingChunk>{
         let mut vec:Vec<&BuildingChunk> = Vec::new();
         match self.divided_chunks {
@@ -69,74 +303,184 @@ impl BuildingChunk{
         }
     }
 
-    pub fn divide(&mut self,parameters:&BuildingIterationParameters){
-        let mut rng = thread_rng();
+    pub fn divide(&mut self,parameters:&BuildingIterationParameters,rng:&mut impl Rng){
         match self.divided_chunks{
             None => {
-                self.divide_evenly(rng.gen_range((parameters.min_rooms_in_split as f64)..( (parameters.max_rooms_in_split as f64)+1.0)) as usize,parameters.is_hallway,parameters.aspect_ratio_probability_factor,parameters.aspect_ratio_probability_offset,!self.horizontal);
+                let is_hallway = parameters.is_hallway && rng.gen_bool(parameters.hallway_branching.clamp(0.0,1.0));
+                self.divide_evenly(rng.gen_range((parameters.min_rooms_in_split as f64)..( (parameters.max_rooms_in_split as f64)+1.0)) as usize,is_hallway,parameters.hallway_width,parameters.aspect_ratio_probability_factor,parameters.aspect_ratio_probability_offset,!self.horizontal,rng);
             }, Some(ref mut data) => {
                 match data{
                     BuildingChunkData::Tagged => {},
                     BuildingChunkData::Parent(ref mut children,..) => {
                         for chunk in children {
-                            chunk.divide(parameters);
+                            chunk.divide(parameters,rng);
                         }
                     }
                 }
             }
         }
     }
-    pub fn divide_evenly(&mut self,room_count:usize,is_hallway:bool,aspect_factor:f32,aspect_offset:f32,horizontal:bool){
+    /// Splits the chunk into `room_count` equal rooms, with a hallway between each pair when `is_hallway` is set.
+    /// Doors are left empty, [`BuildingChunk::connect_rooms`] places them once the layout is done.
+    #[allow(clippy::too_many_arguments)]
+    pub fn divide_evenly(&mut self,room_count:usize,is_hallway:bool,hallway_width:f32,aspect_factor:f32,aspect_offset:f32,horizontal:bool,rng:&mut impl Rng){
         if room_count <= 1{
             return;
         }
-        let mut rng = thread_rng();
-        let room_height = if(horizontal){(self.rect.height() - if (is_hallway){(HALL_WIDTH  * (room_count as f32 - 1.0))} else {0.0})/ (room_count as f32)}else{self.rect.height() };
-        let room_width = if(horizontal){self.rect.width()}else{(self.rect.width() -if (is_hallway){(HALL_WIDTH  * (room_count as f32 - 1.0))} else {0.0}) / (room_count as f32)};
+        let gap = if is_hallway {hallway_width} else {0.0};
+        let room_height = if(horizontal){(self.rect.height() - (gap * (room_count as f32 - 1.0)))/ (room_count as f32)}else{self.rect.height() };
+        let room_width = if(horizontal){self.rect.width()}else{(self.rect.width() - (gap * (room_count as f32 - 1.0))) / (room_count as f32)};
         let aspect = ((room_width/ (room_height + room_width))-0.5).abs();
         if rng.gen_bool((aspect_offset - (aspect*aspect_factor)).clamp(0.0,1.0) as f64) && room_height > MIN_ROOM_DIM && room_width > MIN_ROOM_DIM{
+            let mut children = Vec::new();
             for room in 0..room_count{
-                let doors = if(is_hallway){
-                    let mut doors_temp = [Vec::new(),Vec::new(),Vec::new(),Vec::new()];
-                        if(room > 0) {
-                            if horizontal{
-                                doors_temp[3].push(DoorEnum::Hallway);
-                            }else {
-                                doors_temp[2].push(DoorEnum::Hallway);
-                            }
-
-                        }
-                         if(room < (room_count - 1)){
-                             if horizontal{
-                                 doors_temp[1].push(DoorEnum::Hallway);
-                             }else{
-                                 doors_temp[0].push(DoorEnum::Hallway);
-                             }
-                         }
-                    doors_temp
-                }else{
-                    self.doors.clone()
+                let (room_rect,hallway_rect) = if(horizontal){
+                    let top = self.rect.top() + ((room as f32) * (gap + room_height));
+                    (egui::Rect::from_min_size(Pos2{x:self.rect.left(),y:top},emath::Vec2{x:room_width,y:room_height}),
+                     egui::Rect::from_min_size(Pos2{x:self.rect.left(),y:top + room_height},emath::Vec2{x:room_width,y:gap}))
+                } else{
+                    let left = self.rect.left() + ((room as f32) * (gap + room_width));
+                    (egui::Rect::from_min_size(Pos2{x:left,y:self.rect.top()}, emath::Vec2{x:room_width,y:room_height}),
+                     egui::Rect::from_min_size(Pos2{x:left + room_width,y:self.rect.top()}, emath::Vec2{x:gap,y:room_height}))
                 };
-
-                let room_rect= if(horizontal){
-                    egui::Rect::from_min_size(Pos2{x:self.rect.left(),y:self.rect.top()+ ((room as f32) * ((if (is_hallway){HALL_WIDTH } else {0.0})+room_height))},emath::Vec2{x:room_width,y:room_height})}
-                else{
-                    egui::Rect::from_min_size(Pos2{x:self.rect.left()+ ((room as f32 )* ((if (is_hallway){HALL_WIDTH} else {0.0})+room_width)),y:self.rect.top()}, emath::Vec2{x:room_width,y:room_height}) };
-
-                match self.divided_chunks{
-                    Some(ref mut chunk_data)=>{
-                        if let BuildingChunkData::Parent(ref mut children, ..) = chunk_data {
-                            children.push(BuildingChunk{rect:room_rect, divided_chunks: None, doors, horizontal });
-                        }
+                children.push(BuildingChunk::new(room_rect, Default::default(), horizontal));
+                if is_hallway && room < (room_count - 1){
+                    // Hallways are never divided any further
+                    let mut hallway = BuildingChunk::new(hallway_rect, Default::default(), horizontal).with_kind(RoomKind::Hallway);
+                    hallway.divided_chunks = Some(BuildingChunkData::Tagged);
+                    children.push(hallway);
+                }
+            }
+            match self.divided_chunks{
+                Some(BuildingChunkData::Parent(ref mut existing, ..))=>{
+                    existing.append(&mut children);
+                }
+                _=>{
+                    self.divided_chunks = Some(BuildingChunkData::Parent(children, is_hallway));
+                }
+            };
+        }
+    }
+    fn leaves_mut(&mut self)->Vec<&mut BuildingChunk>{
+        match self.divided_chunks{
+            Some(BuildingChunkData::Parent(ref mut children, _)) => children.iter_mut().flat_map(|child| child.leaves_mut()).collect(),
+            _ => vec![self],
+        }
+    }
+    /// Replaces the doors of every room so the building forms a single network.
+    ///
+    /// Hallways open onto each other and every room facing a hallway opens onto it. Whatever
+    /// is still cut off after that gets a plain door to a neighbour, and the front door goes on an
+    /// outside wall, of a hallway if there is one. Each door sits in the middle of the stretch of
+    /// wall the two rooms share, so every neighbour along a side with room for a door can be
+    /// reached, and each pair of rooms gets at most one.
+    pub fn connect_rooms(&mut self){
+        let mut leaves = self.leaves_mut();
+        let rooms:Vec<(egui::Rect,bool,i32)> = leaves.iter().map(|leaf|(leaf.rect,leaf.kind == RoomKind::Hallway,leaf.level)).collect();
+        let mut neighbours = Vec::new();
+        let mut outside = Vec::new();
+        for (index,(rect,_,level)) in rooms.iter().enumerate(){
+            for dir in 0..4{
+                let mut spans = Vec::new();
+                for (other_index,(other,_,other_level)) in rooms.iter().enumerate(){
+                    if other_index == index || other_level != level{
+                        continue;
                     }
-                    None=>{
-                        self.divided_chunks = Some(BuildingChunkData::Parent(vec![ BuildingChunk { rect: room_rect, divided_chunks: None, doors, horizontal }], is_hallway));
+                    let Some(span) = shared_span(*rect,*other,dir) else {
+                        continue;
+                    };
+                    if door_fits(span){
+                        neighbours.push((index,dir,other_index,span.center()));
                     }
-                };
+                    spans.push(span);
+                }
+                // The front door needs a stretch of the side with nothing on the other side of it
+                let side = if dir % 2 == 0 {rect.height()} else {rect.width()};
+                if let Some(gap) = uncovered(side,spans).into_iter().find(|gap| door_fits(*gap)){
+                    outside.push((index,dir,gap.center()));
+                }
             }
+        }
+        for leaf in leaves.iter_mut(){
+            leaf.doors = Default::default();
+            leaf.door_offsets = Default::default();
+        }
+        // Union-find over rooms joined by a door so far
+        let mut networks:Vec<usize> = (0..rooms.len()).collect();
+        fn network(networks:&mut Vec<usize>,room:usize)->usize{
+            if networks[room] != room{
+                let root = network(networks,networks[room]);
+                networks[room] = root;
+            }
+            networks[room]
+        }
+        // Both rooms see the wall they share, hallways joining each other keep the door on the first
+        for &(from,dir,to,offset) in &neighbours{
+            if rooms[to].1 && !(rooms[from].1 && from > to){
+                leaves[from].doors[dir].push(DoorEnum::Hallway);
+                leaves[from].door_offsets[dir].push(offset);
+                let root = network(&mut networks,to);
+                let from_root = network(&mut networks,from);
+                networks[from_root] = root;
+            }
+        }
+        for &(from,dir,to,offset) in &neighbours{
+            let root = network(&mut networks,to);
+            let from_root = network(&mut networks,from);
+            if root != from_root{
+                leaves[from].doors[dir].push(DoorEnum::Interior(false));
+                leaves[from].door_offsets[dir].push(offset);
+                networks[from_root] = root;
+            }
+        }
+        let front_door = outside.iter()
+            .filter(|(from,..)| rooms[*from].2 == 0)
+            .min_by_key(|(from,..)| !rooms[*from].1);
+        if let Some(&(from,dir,offset)) = front_door{
+            leaves[from].doors[dir].push(DoorEnum::Exterior);
+            leaves[from].door_offsets[dir].push(offset);
+        }
+    }
+}
+
+/// The stretch of the side of `rect` facing `dir` that `other` lies against, measured from the
+/// start of that side the same way as door offsets.
+fn shared_span(rect:egui::Rect,other:egui::Rect,dir:usize)->Option<Rangef>{
+    // How far past the side `other` starts, and where both run along it
+    let (gap,along,other_along,start) = match dir{
+        0 => (other.min.x - rect.max.x,rect.y_range(),other.y_range(),rect.min.y),
+        1 => (other.min.y - rect.max.y,rect.x_range(),other.x_range(),rect.min.x),
+        2 => (rect.min.x - other.max.x,rect.y_range(),other.y_range(),rect.min.y),
+        _ => (rect.min.y - other.max.y,rect.x_range(),other.x_range(),rect.min.x),
+    };
+    // Rooms split side by side only line up to within rounding
+    if !(-0.001..=WALL_THICKNESS).contains(&gap){
+        return None;
+    }
+    let span = Rangef::new(along.min.max(other_along.min) - start,along.max.min(other_along.max) - start);
+    (span.span() > 0.0).then_some(span)
+}
 
+/// The parts of a side `length` long that none of `spans` cover.
+fn uncovered(length:f32,mut spans:Vec<Rangef>)->Vec<Rangef>{
+    spans.sort_by(|a,b| a.min.total_cmp(&b.min));
+    let mut gaps = Vec::new();
+    let mut covered = 0.0;
+    for span in spans{
+        if span.min > covered{
+            gaps.push(Rangef::new(covered,span.min));
         }
+        covered = f32::max(covered,span.max);
     }
+    if covered < length{
+        gaps.push(Rangef::new(covered,length));
+    }
+    gaps
+}
+
+/// Whether a door fits in a stretch of wall, clear of the walls meeting it at either end.
+fn door_fits(span:Rangef)->bool{
+    span.span() >= DOOR_WIDTH + 2.0 * WALL_THICKNESS
 }
 
 #[derive(Debug)]
@@ -165,14 +509,16 @@ pub struct BuildingIterationParameters{
     pub min_rooms_in_split:usize,
     pub max_rooms_in_split:usize,
     pub is_hallway:bool,
+    pub hallway_width:f32,
+    /// Chance of each split on this iteration being separated by a hallway, when `is_hallway` is set.
+    pub hallway_branching:f64,
     pub aspect_ratio_probability_factor:f32,
     pub aspect_ratio_probability_offset:f32,
     pub room_requirements:Vec<RoomSpec>,
 }
 const MINIMUM_BUILDING_SIZE:f32 = 300.0;
-pub fn generate_building(room_iters:Vec<(BuildingIterationParameters,usize)>) -> BuildingChunk {
+pub fn generate_building(room_iters:Vec<(BuildingIterationParameters,usize)>,rng:&mut impl Rng) -> BuildingChunk {
     let mut area = 0.0;
-    let mut rng = thread_rng();
     for iter in &room_iters{
         for room in &iter.0.room_requirements{
             area += (room.area_range.max + room.area_range.min)/2.0;
@@ -182,14 +528,14 @@ pub fn generate_building(room_iters:Vec<(BuildingIterationParameters,usize)>) ->
     area = area.max(MINIMUM_BUILDING_SIZE);
     let theta=rng.gen_range(((PI/4.0)-BUILDING_ASPECT_VARIATION)..((PI/4.0)+BUILDING_ASPECT_VARIATION));
     let scale = rng.gen_range(1.0..(1.0 + BUILDING_SIZE_VARIATION)) ;
-    let mut building = BuildingChunk { rect:egui::Rect{min:Pos2{x:0.0,y:0.0},max:Pos2{x:area.sqrt() * theta.cos() * scale,y:area.sqrt() * theta.sin() * scale}},
-        divided_chunks: None,
-        doors:[vec![DoorEnum::Exterior],Vec::new(),Vec::new(),Vec::new()],
-        horizontal: false };
-    building.divide_evenly(2, true, 0.0, 1.0, false);
+    let mut building = BuildingChunk::new(egui::Rect{min:Pos2{x:0.0,y:0.0},max:Pos2{x:area.sqrt() * theta.cos() * scale,y:area.sqrt() * theta.sin() * scale}},
+        [vec![DoorEnum::Exterior],Vec::new(),Vec::new(),Vec::new()],
+        false);
+    let hallway_width = room_iters.first().map_or(HALL_WIDTH, |(parameters, _)| parameters.hallway_width);
+    building.divide_evenly(2, true, hallway_width, 0.0, 1.0, false, rng);
     for specs in room_iters{
         for n in 0..specs.1{
-            building.divide(&specs.0);
+            building.divide(&specs.0,rng);
         }
         for mut room_requirement in specs.0.room_requirements{
             if(room_requirement.room.is_none()){
@@ -210,6 +556,6 @@ pub fn generate_building(room_iters:Vec<(BuildingIterationParameters,usize)>) ->
             }
         }
     }
-
+    building.connect_rooms();
     building
 }
\ No newline at end of file
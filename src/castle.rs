@@ -0,0 +1,106 @@
+use std::ops::Range;
+
+use bevy_egui::egui;
+use bevy_egui::egui::{Pos2, Rangef};
+use rand::Rng;
+
+use crate::building::{BuildingChunk, BuildingChunkData, DoorEnum, RoomKind, RoomShape};
+
+/// Parameters for the gothic castle layout strategy.
+///
+/// A castle is a ring of long galleries around an open courtyard, with a tower on
+/// every corner. The gallery opposite the gate is the keep, which is split into
+/// rooms and may have crypts dug out below it.
+///
+/// Every doorway is declared by only one of the rooms it joins, the other gets its
+/// opening cut from the same door: towers declare the doors into their neighbours,
+/// and each keep room or crypt the door into the one west of it.
+pub struct CastleParameters {
+    pub width_range: Rangef,
+    pub depth_range: Rangef,
+    pub gallery_width: f32,
+    /// Must lie between `gallery_width` and twice of it so towers still touch their galleries.
+    pub tower_diameter: f32,
+    pub tower_shape: RoomShape,
+    pub keep_rooms: Range<usize>,
+    pub has_crypts: bool,
+}
+
+impl Default for CastleParameters {
+    fn default() -> Self {
+        Self {
+            width_range: Rangef::new(30.0, 45.0),
+            depth_range: Rangef::new(25.0, 40.0),
+            gallery_width: 5.0,
+            tower_diameter: 7.0,
+            tower_shape: RoomShape::Octagonal,
+            keep_rooms: 2..5,
+            has_crypts: true,
+        }
+    }
+}
+
+fn doors(right: Vec<DoorEnum>, bottom: Vec<DoorEnum>, left: Vec<DoorEnum>, top: Vec<DoorEnum>) -> [Vec<DoorEnum>; 4] {
+    [right, bottom, left, top]
+}
+
+pub fn generate_castle(parameters: &CastleParameters, rng: &mut impl Rng) -> BuildingChunk {
+    let width = rng.gen_range(parameters.width_range.min..=parameters.width_range.max);
+    let depth = rng.gen_range(parameters.depth_range.min..=parameters.depth_range.max);
+    let gallery = parameters.gallery_width;
+    let overhang = (parameters.tower_diameter - gallery).clamp(0.0, gallery);
+    let bounds = egui::Rect::from_min_max(Pos2::ZERO, Pos2 { x: width, y: depth });
+    let mut children = vec![
+        BuildingChunk::new(bounds.shrink(gallery), doors(vec![], vec![], vec![], vec![]), false)
+            .with_kind(RoomKind::Courtyard),
+        // Galleries along the west, east and south (gate) walls
+        BuildingChunk::new(egui::Rect::from_min_max(Pos2 { x: 0.0, y: gallery }, Pos2 { x: gallery, y: depth - gallery }),
+                           doors(vec![DoorEnum::Hallway], vec![], vec![], vec![]), true)
+            .with_kind(RoomKind::Gallery)
+            .with_battlements(true),
+        BuildingChunk::new(egui::Rect::from_min_max(Pos2 { x: width - gallery, y: gallery }, Pos2 { x: width, y: depth - gallery }),
+                           doors(vec![], vec![], vec![DoorEnum::Hallway], vec![]), true)
+            .with_kind(RoomKind::Gallery)
+            .with_battlements(true),
+        BuildingChunk::new(egui::Rect::from_min_max(Pos2 { x: gallery, y: depth - gallery }, Pos2 { x: width - gallery, y: depth }),
+                           doors(vec![], vec![DoorEnum::Exterior], vec![], vec![DoorEnum::Hallway]), false)
+            .with_kind(RoomKind::Gallery)
+            .with_battlements(true),
+    ];
+
+    // The keep runs along the north wall, opposite the gate
+    let keep_rooms = rng.gen_range(parameters.keep_rooms.clone()).max(1);
+    let room_width = (width - 2.0 * gallery) / keep_rooms as f32;
+    for room in 0..keep_rooms {
+        let rect = egui::Rect::from_min_size(Pos2 { x: gallery + room as f32 * room_width, y: 0.0 }, egui::Vec2 { x: room_width, y: gallery });
+        // The first room is reached from the north west tower
+        let left = if room > 0 { vec![DoorEnum::Hallway] } else { vec![] };
+        let top = if parameters.has_crypts { vec![DoorEnum::Stairs(-1)] } else { vec![] };
+        children.push(BuildingChunk::new(rect, doors(vec![], vec![DoorEnum::Hallway], left.clone(), top), false)
+            .with_battlements(true));
+        if parameters.has_crypts {
+            children.push(BuildingChunk::new(rect, doors(vec![], vec![], left, vec![DoorEnum::Stairs(0)]), false)
+                .with_kind(RoomKind::Crypt)
+                .with_level(-1));
+        }
+    }
+
+    // Corner towers, pushed outwards when wider than the galleries they join
+    let tower_size = egui::Vec2::splat(gallery + overhang);
+    let towers = [
+        (Pos2 { x: -overhang, y: -overhang }, doors(vec![DoorEnum::Hallway], vec![DoorEnum::Hallway], vec![], vec![])),
+        (Pos2 { x: width - gallery, y: -overhang }, doors(vec![], vec![DoorEnum::Hallway], vec![DoorEnum::Hallway], vec![])),
+        (Pos2 { x: width - gallery, y: depth - gallery }, doors(vec![], vec![], vec![DoorEnum::Hallway], vec![DoorEnum::Hallway])),
+        (Pos2 { x: -overhang, y: depth - gallery }, doors(vec![DoorEnum::Hallway], vec![], vec![], vec![DoorEnum::Hallway])),
+    ];
+    for (min, tower_doors) in towers {
+        children.push(BuildingChunk::new(egui::Rect::from_min_size(min, tower_size), tower_doors, false)
+            .with_kind(RoomKind::Tower)
+            .with_shape(parameters.tower_shape)
+            .with_battlements(true));
+    }
+
+    let mut castle = BuildingChunk::new(bounds, doors(vec![], vec![DoorEnum::Exterior], vec![], vec![]), false);
+    castle.divided_chunks = Some(BuildingChunkData::Parent(children, false));
+    castle
+}
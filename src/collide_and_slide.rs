@@ -0,0 +1,98 @@
+//! Collide-and-slide movement for kinematic characters.
+//!
+//! A move is swept ahead with shape casts before it happens. When something is in the way the
+//! character stops just short of it and the rest of the move is projected onto the surface it hit,
+//! so it slides along walls instead of sinking into or tunnelling through them.
+
+use bevy::prelude::*;
+
+/// How many surfaces a single move can slide along before the rest of it is dropped.
+pub const MAX_SLIDE_ITERATIONS: usize = 4;
+/// Gap kept between the character and whatever it hits, so the next cast doesn't start out touching.
+pub const SKIN_WIDTH: f32 = 0.01;
+
+/// What a cast ran into. `normal` points out of the surface, back towards the character.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlideHit {
+    pub distance: f32,
+    pub normal: Vec3,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SlideResult {
+    pub position: Vec3,
+    /// Every surface hit during the move, in order.
+    pub normals: Vec<Vec3>,
+}
+
+impl SlideResult {
+    /// Removes the part of `velocity` heading into any of the surfaces hit.
+    pub fn clip_velocity(&self, mut velocity: Vec3) -> Vec3 {
+        for normal in &self.normals {
+            let into = velocity.dot(*normal);
+            if into < 0.0 {
+                velocity -= *normal * into;
+            }
+        }
+        velocity
+    }
+
+    fn distance_along(&self, origin: Vec3, motion: Vec3) -> f32 {
+        (self.position - origin).dot(motion.normalize_or_zero())
+    }
+}
+
+/// Moves from `position` by `motion`. `cast` sweeps the character's shape from an origin along a
+/// direction up to a distance, returning the first thing in the way.
+pub fn collide_and_slide(mut position: Vec3,
+                         mut motion: Vec3,
+                         mut cast: impl FnMut(Vec3, Direction3d, f32) -> Option<SlideHit>) -> SlideResult {
+    let mut normals: Vec<Vec3> = Vec::new();
+    for _ in 0..MAX_SLIDE_ITERATIONS {
+        let distance = motion.length();
+        let Ok(direction) = Direction3d::new(motion) else {
+            break;
+        };
+        let Some(hit) = cast(position, direction, distance + SKIN_WIDTH) else {
+            position += motion;
+            break;
+        };
+        let travel = (hit.distance - SKIN_WIDTH).clamp(0.0, distance);
+        position += *direction * travel;
+        let remaining = motion - *direction * travel;
+        motion = remaining - hit.normal * remaining.dot(hit.normal);
+        // Keep out of surfaces hit earlier in the move too, so corners don't bounce between walls
+        for normal in &normals {
+            let into = motion.dot(*normal);
+            if into < 0.0 {
+                motion -= *normal * into;
+            }
+        }
+        normals.push(hit.normal);
+    }
+    SlideResult { position, normals }
+}
+
+/// Tries to climb a ledge of up to `height` that blocked the horizontal part of `motion`, by moving up,
+/// across and back down again. Returns the stepped move if it got further than sliding did.
+pub fn step_up(position: Vec3,
+               motion: Vec3,
+               slid: &SlideResult,
+               height: f32,
+               max_slope_angle: f32,
+               mut cast: impl FnMut(Vec3, Direction3d, f32) -> Option<SlideHit>) -> Option<SlideResult> {
+    let horizontal = Vec3::new(motion.x, 0.0, motion.z);
+    let blocked = slid.normals.iter().any(|normal| normal.angle_between(Vec3::Y) > max_slope_angle);
+    if !blocked || horizontal == Vec3::ZERO {
+        return None;
+    }
+    let up = collide_and_slide(position, Vec3::Y * height, &mut cast);
+    let across = collide_and_slide(up.position, horizontal, &mut cast);
+    let mut down = collide_and_slide(across.position, Vec3::NEG_Y * height, &mut cast);
+    let landed = down.normals.iter().any(|normal| normal.angle_between(Vec3::Y) <= max_slope_angle);
+    if !landed || down.distance_along(position, horizontal) <= slid.distance_along(position, horizontal) + SKIN_WIDTH {
+        return None;
+    }
+    down.normals.extend(across.normals);
+    Some(down)
+}
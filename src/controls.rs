@@ -0,0 +1,236 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::egui;
+use bevy_persistent::Persistent;
+use leafwing_input_manager::axislike::AxisType;
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::kinematic_character_3d::{CharacterController, PlayerMovement};
+use crate::Settings;
+
+/// Stick tuning, kept apart from the mouse settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GamepadSettings {
+    /// How far a stick has to move before it counts, from 0 to 1.
+    pub deadzone: f32,
+    /// Radians per second the camera turns at with the stick all the way over.
+    pub look_speed: f32,
+    /// Scales how fast the stick moves the virtual pointer.
+    pub pointer_speed: f32,
+}
+
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        Self { deadzone: 0.15, look_speed: 3.0, pointer_speed: 1.0 }
+    }
+}
+
+/// Bindings used until the player rebinds anything.
+pub fn default_input_map() -> InputMap<PlayerMovement> {
+    let mut input_map = InputMap::default();
+    input_map.insert(PlayerMovement::Move, VirtualDPad::wasd());
+    input_map.insert(PlayerMovement::UIToggle, KeyCode::ShiftLeft);
+    input_map.insert(PlayerMovement::Pause, KeyCode::Escape);
+    input_map.insert(PlayerMovement::Click, InputKind::Mouse(MouseButton::Left));
+    input_map.insert(PlayerMovement::Look, DualAxis::mouse_motion());
+    input_map.insert(PlayerMovement::Interact, KeyCode::KeyE);
+    input_map.insert(PlayerMovement::Jump, KeyCode::Space);
+    input_map.insert(PlayerMovement::Sprint, KeyCode::ControlLeft);
+    input_map.insert(PlayerMovement::Crouch, KeyCode::KeyC);
+    input_map.insert(PlayerMovement::CycleCamera, KeyCode::KeyV);
+
+    input_map.insert(PlayerMovement::Move, DualAxis::left_stick());
+    input_map.insert(PlayerMovement::StickLook, DualAxis::right_stick());
+    // Held like Left Shift to bring up the virtual pointer, then the right trigger drags a box
+    input_map.insert(PlayerMovement::UIToggle, GamepadButtonType::LeftTrigger2);
+    input_map.insert(PlayerMovement::Click, GamepadButtonType::RightTrigger2);
+    input_map.insert(PlayerMovement::Pause, GamepadButtonType::Start);
+    input_map.insert(PlayerMovement::Interact, GamepadButtonType::West);
+    input_map.insert(PlayerMovement::Jump, GamepadButtonType::South);
+    input_map.insert(PlayerMovement::Sprint, GamepadButtonType::LeftThumb);
+    input_map.insert(PlayerMovement::Crouch, GamepadButtonType::East);
+    input_map.insert(PlayerMovement::CycleCamera, GamepadButtonType::Select);
+    apply_deadzone(&mut input_map, GamepadSettings::default().deadzone);
+    input_map
+}
+
+/// Sets the deadzone of every stick binding.
+pub fn apply_deadzone(controls: &mut InputMap<PlayerMovement>, deadzone: f32) {
+    for action in PlayerMovement::iter() {
+        for input in controls.get_mut(&action).into_iter().flatten() {
+            if let UserInput::Single(InputKind::DualAxis(axis)) = input {
+                if is_gamepad(&InputKind::DualAxis(*axis)) {
+                    axis.deadzone = DeadZoneShape::Ellipse { radius_x: deadzone, radius_y: deadzone };
+                }
+            }
+        }
+    }
+}
+
+/// Short name for a binding, as shown in prompts and the controls page.
+pub fn input_label(input: &UserInput) -> String {
+    input.to_string().trim_start_matches("Key").to_string()
+}
+
+/// The buttons and keys that make up a binding.
+fn input_kinds(input: &UserInput) -> Vec<InputKind> {
+    match input {
+        UserInput::Single(kind) => vec![*kind],
+        UserInput::Chord(kinds) => kinds.clone(),
+        UserInput::VirtualDPad(dpad) => vec![dpad.up, dpad.down, dpad.left, dpad.right],
+        UserInput::VirtualAxis(axis) => vec![axis.negative, axis.positive],
+    }
+}
+
+fn is_gamepad(kind: &InputKind) -> bool {
+    match kind {
+        InputKind::GamepadButton(_) => true,
+        InputKind::SingleAxis(axis) => matches!(axis.axis_type, AxisType::Gamepad(_)),
+        InputKind::DualAxis(axis) => matches!(axis.x.axis_type, AxisType::Gamepad(_)),
+        _ => false,
+    }
+}
+
+/// Buttons the player can press while a binding is being captured.
+#[derive(SystemParam)]
+pub struct RebindInput<'w> {
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    mouse_buttons: Res<'w, ButtonInput<MouseButton>>,
+    gamepad_buttons: Res<'w, ButtonInput<GamepadButton>>,
+}
+
+impl RebindInput<'_> {
+    /// Mouse buttons only count away from egui, so clicking the settings window doesn't bind them.
+    fn just_pressed(&self, ctx: &egui::Context) -> Option<InputKind> {
+        self.keys.get_just_pressed().next().map(|key| InputKind::PhysicalKey(*key))
+            .or_else(|| self.mouse_buttons.get_just_pressed().next().filter(|_| !ctx.is_pointer_over_area()).map(|button| InputKind::Mouse(*button)))
+            .or_else(|| self.gamepad_buttons.get_just_pressed().next().map(|button| InputKind::GamepadButton(button.button_type)))
+    }
+}
+
+/// A binding in the middle of being captured from the controls page.
+#[derive(Default)]
+pub struct Rebinding {
+    action: Option<PlayerMovement>,
+    /// Keys pressed so far, `Move` takes four of them: up, down, left and right.
+    captured: Vec<InputKind>,
+    /// A captured key that's already bound to another action, waiting on the player to decide.
+    conflict: Option<(InputKind, PlayerMovement)>,
+}
+
+impl Rebinding {
+    pub fn is_capturing(&self) -> bool {
+        self.action.is_some()
+    }
+}
+
+const DPAD_DIRECTIONS: [&str; 4] = ["up", "down", "left", "right"];
+
+/// Replaces the bindings of `action` from the same kind of device as `captured`, keeping the others.
+fn bind(controls: &mut InputMap<PlayerMovement>, action: PlayerMovement, captured: &[InputKind]) {
+    let input = if action == PlayerMovement::Move {
+        UserInput::VirtualDPad(VirtualDPad { up: captured[0], down: captured[1], left: captured[2], right: captured[3] })
+    } else {
+        UserInput::Single(captured[0])
+    };
+    let gamepad = is_gamepad(&captured[0]);
+    let mut inputs = controls.get(&action).cloned().unwrap_or_default();
+    inputs.retain(|existing| input_kinds(existing).iter().any(is_gamepad) != gamepad);
+    inputs.push(input);
+    controls.clear_action(&action);
+    for input in inputs {
+        controls.insert(action, input);
+    }
+}
+
+/// Drops every binding of `action` that uses `kind`.
+fn unbind(controls: &mut InputMap<PlayerMovement>, action: PlayerMovement, kind: InputKind) {
+    if let Some(inputs) = controls.get_mut(&action) {
+        inputs.retain(|input| !input_kinds(input).contains(&kind));
+    }
+}
+
+fn bound_to(controls: &InputMap<PlayerMovement>, kind: InputKind, except: PlayerMovement) -> Option<PlayerMovement> {
+    controls.iter()
+        .find(|(action, inputs)| **action != except && inputs.iter().any(|input| input_kinds(input).contains(&kind)))
+        .map(|(action, _)| *action)
+}
+
+/// The controls page of the settings window. Returns true once a binding has changed.
+pub fn controls_ui(ui: &mut egui::Ui, controls: &mut InputMap<PlayerMovement>, rebinding: &mut Rebinding, input: &RebindInput) -> bool {
+    let mut changed = false;
+    if let (Some(action), None) = (rebinding.action, rebinding.conflict) {
+        if let Some(kind) = input.just_pressed(ui.ctx()) {
+            match bound_to(controls, kind, action) {
+                Some(other) => rebinding.conflict = Some((kind, other)),
+                None => rebinding.captured.push(kind),
+            }
+        }
+    }
+    if let Some((kind, other)) = rebinding.conflict {
+        ui.colored_label(egui::Color32::YELLOW, format!("{} is already bound to {other}", input_label(&UserInput::Single(kind))));
+        ui.horizontal(|ui| {
+            if ui.button(format!("Unbind from {other}")).clicked() {
+                unbind(controls, other, kind);
+                rebinding.captured.push(kind);
+                rebinding.conflict = None;
+                changed = true;
+            }
+            if ui.button("Pick another").clicked() {
+                rebinding.conflict = None;
+            }
+        });
+    }
+    if let Some(action) = rebinding.action {
+        let needed = if action == PlayerMovement::Move { DPAD_DIRECTIONS.len() } else { 1 };
+        if rebinding.captured.len() >= needed {
+            bind(controls, action, &rebinding.captured);
+            *rebinding = Rebinding::default();
+            changed = true;
+        }
+    }
+    egui::Grid::new("controls").striped(true).show(ui, |ui| {
+        for action in PlayerMovement::iter() {
+            ui.label(action.to_string());
+            let bindings = controls.get(&action).map(|inputs| inputs.iter().map(input_label).collect::<Vec<_>>().join(", "));
+            ui.label(bindings.unwrap_or_else(|| "Unbound".to_string()));
+            if matches!(action, PlayerMovement::Look | PlayerMovement::StickLook) {
+                ui.label("");
+            } else if rebinding.action == Some(action) {
+                let prompt = if action == PlayerMovement::Move { format!("Press {}...", DPAD_DIRECTIONS[rebinding.captured.len()]) } else { "Press a key or click outside...".to_string() };
+                if ui.button(prompt).on_hover_text("Click to cancel").clicked() {
+                    *rebinding = Rebinding::default();
+                }
+            } else if ui.add_enabled(!rebinding.is_capturing(), egui::Button::new("Rebind")).clicked() {
+                rebinding.action = Some(action);
+            }
+            ui.end_row();
+        }
+    });
+    if ui.button("Reset to defaults").clicked() {
+        *controls = default_input_map();
+        *rebinding = Rebinding::default();
+        changed = true;
+    }
+    changed
+}
+
+/// Keeps the player's bindings in step with the saved ones, including when the player is first spawned.
+fn apply_controls(settings: Res<Persistent<Settings>>,
+                  mut q_player: Query<&mut InputMap<PlayerMovement>, With<CharacterController>>) {
+    for mut input_map in &mut q_player {
+        if *input_map != settings.controls {
+            *input_map = settings.controls.clone();
+        }
+    }
+}
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, apply_controls.run_if(resource_exists::<Persistent<Settings>>));
+    }
+}
@@ -0,0 +1,82 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use crate::DOOR_WIDTH;
+use crate::interactable::InteractableAction;
+
+pub const DOOR_OPEN_ACTION: &str = "open";
+pub const DOOR_CLOSE_ACTION: &str = "close";
+const DOOR_OPEN_ANGLE: f32 = PI / 2.0;
+/// How fast doors swing, in radians per second.
+const DOOR_SWING_SPEED: f32 = 3.0;
+
+/// Whether a door is open, shut or locked shut. Hinged doors swing to match it.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DoorState {
+    Open,
+    #[default]
+    Closed,
+    Locked,
+}
+
+/// Swings a door about the hinge on one of its edges.
+#[derive(Component, Debug, Clone)]
+pub struct DoorHinge {
+    closed: Transform,
+    /// +1 or -1, so the door always opens into the room it belongs to.
+    swing: f32,
+    angle: f32,
+}
+
+impl DoorHinge {
+    pub fn new(closed: Transform, inside: Vec3) -> Self {
+        // The free edge of the door moves towards local +x for a positive angle
+        let swing = if (closed.rotation * Vec3::X).dot(inside) >= 0.0 { 1.0 } else { -1.0 };
+        Self { closed, swing, angle: 0.0 }
+    }
+
+    fn hinge_point(&self) -> Vec3 {
+        self.closed.translation + self.closed.rotation * Vec3::new(0.0, 0.0, -DOOR_WIDTH / 2.0)
+    }
+}
+
+pub struct DoorPlugin;
+
+impl Plugin for DoorPlugin {
+    fn build(&self, app: &mut App) {
+        // Sent by the selection window, which isn't there without a UI
+        app.add_event::<InteractableAction>()
+            .add_systems(Update, (door_batch_actions, swing_doors).chain());
+    }
+}
+
+fn swing_doors(time: Res<Time>,
+               mut q_doors: Query<(&DoorState, &mut DoorHinge, &mut Transform)>) {
+    for (state, mut hinge, mut transform) in &mut q_doors {
+        let target = if *state == DoorState::Open { DOOR_OPEN_ANGLE } else { 0.0 };
+        if hinge.angle == target {
+            continue;
+        }
+        let step = DOOR_SWING_SPEED * time.delta_seconds();
+        hinge.angle = if hinge.angle < target { (hinge.angle + step).min(target) } else { (hinge.angle - step).max(target) };
+        let hinge_point = hinge.hinge_point();
+        *transform = hinge.closed;
+        transform.rotate_around(hinge_point, Quat::from_rotation_y(hinge.angle * hinge.swing));
+    }
+}
+
+fn door_batch_actions(mut actions: EventReader<InteractableAction>,
+                      mut q_doors: Query<&mut DoorState, With<DoorHinge>>) {
+    for action in actions.read() {
+        let Ok(mut state) = q_doors.get_mut(action.entity) else {
+            continue;
+        };
+        // Locked doors stay shut, they have to be dealt with one at a time
+        match (action.action, *state) {
+            (DOOR_OPEN_ACTION, DoorState::Closed) => *state = DoorState::Open,
+            (DOOR_CLOSE_ACTION, DoorState::Open) => *state = DoorState::Closed,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Hand-authored floor plans, for story beats that need an exact layout.
+//!
+//! A plan is a plain text file made of room definitions followed by a grid:
+//!
+//! ```text
+//! // Comments start with two slashes
+//! scale = 1.0
+//! a = Harker's office
+//! b = Stair tower | kind=tower | shape=octagonal
+//!
+//! ##########
+//! #aaaa#bbb#
+//! #aaaaDbbb#
+//! ###E######
+//! ```
+//!
+//! Every lowercase letter in the grid is a room cell, and each letter must form a
+//! single rectangle. `#` is a wall, `.` or a space is outside. Doors are placed in
+//! the wall cells around a room: `E` exterior, `H` open hallway, `D` interior and
+//! `L` locked interior. Each door is cut exactly where it's drawn, and belongs to the
+//! first room next to it in alphabetical order, which it opens into and locks from.
+//!
+//! Definitions name a room with a tag and can set its `kind`, `shape` and `level`.
+//! `key=b` leaves the key to room `b` lying in the room, which must then have a tag:
+//! it fits every door belonging to `b`.
+//! `scale` sets how many world units a grid cell covers.
+
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+use bevy::utils::{HashMap, HashSet};
+use bevy_egui::egui;
+use bevy_egui::egui::Pos2;
+
+use crate::building::{BuildingChunk, BuildingChunkData, DoorEnum, RoomKind, RoomShape};
+
+#[derive(Debug)]
+pub enum FloorPlanError {
+    Io(std::io::Error),
+    InvalidDefinition { line: usize },
+    UnknownCell { cell: char, row: usize, column: usize },
+    NonRectangularRoom(char),
+    NoRooms,
+}
+
+impl Display for FloorPlanError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FloorPlanError::Io(error) => write!(f, "could not read floor plan: {error}"),
+            FloorPlanError::InvalidDefinition { line } => write!(f, "invalid definition on line {line}"),
+            FloorPlanError::UnknownCell { cell, row, column } => write!(f, "unknown cell '{cell}' at row {row}, column {column}"),
+            FloorPlanError::NonRectangularRoom(room) => write!(f, "room '{room}' is not a single rectangle"),
+            FloorPlanError::NoRooms => write!(f, "floor plan has no rooms"),
+        }
+    }
+}
+
+impl std::error::Error for FloorPlanError {}
+
+#[derive(Default)]
+struct RoomDefinition {
+    tag: Option<String>,
+    kind: RoomKind,
+    shape: RoomShape,
+    level: i32,
+    /// Rooms whose key lies in this one, with the line that put it there.
+    keys: Vec<(char, usize)>,
+}
+
+fn door_from_cell(cell: char) -> Option<DoorEnum> {
+    match cell {
+        'E' => Some(DoorEnum::Exterior),
+        'H' => Some(DoorEnum::Hallway),
+        'D' => Some(DoorEnum::Interior(false)),
+        'L' => Some(DoorEnum::Interior(true)),
+        _ => None,
+    }
+}
+
+pub fn load_floor_plan(path: impl AsRef<Path>) -> Result<BuildingChunk, FloorPlanError> {
+    parse_floor_plan(&std::fs::read_to_string(path).map_err(FloorPlanError::Io)?)
+}
+
+pub fn parse_floor_plan(text: &str) -> Result<BuildingChunk, FloorPlanError> {
+    let mut scale = 1.0;
+    let mut definitions: HashMap<char, RoomDefinition> = HashMap::new();
+    let mut grid: Vec<Vec<char>> = Vec::new();
+    for (line_index, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("//") || (trimmed.is_empty() && grid.is_empty()) {
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once('=') else {
+            grid.push(line.trim_end().chars().collect());
+            continue;
+        };
+        let invalid = || FloorPlanError::InvalidDefinition { line: line_index + 1 };
+        let key = key.trim();
+        if key == "scale" {
+            scale = value.trim().parse().map_err(|_| invalid())?;
+            continue;
+        }
+        let mut key_chars = key.chars();
+        let (Some(room), None) = (key_chars.next(), key_chars.next()) else {
+            return Err(invalid());
+        };
+        if !room.is_ascii_lowercase() {
+            return Err(invalid());
+        }
+        let mut options = value.split('|');
+        let mut definition = RoomDefinition {
+            tag: options.next().map(|tag| tag.trim().to_string()).filter(|tag| !tag.is_empty()),
+            ..Default::default()
+        };
+        for option in options {
+            let Some((name, value)) = option.split_once('=') else {
+                return Err(invalid());
+            };
+            let value = value.trim();
+            match name.trim() {
+                "kind" => definition.kind = RoomKind::from_str(value).map_err(|_| invalid())?,
+                "shape" => definition.shape = RoomShape::from_str(value).map_err(|_| invalid())?,
+                "level" => definition.level = value.parse().map_err(|_| invalid())?,
+                "key" => match value.parse::<char>() {
+                    Ok(key_room) if key_room.is_ascii_lowercase() => definition.keys.push((key_room, line_index + 1)),
+                    _ => return Err(invalid()),
+                },
+                _ => return Err(invalid()),
+            }
+        }
+        definitions.insert(room, definition);
+    }
+
+    // Collect the cells of every room, checking the rest of the grid as we go
+    let mut rooms: HashMap<char, Vec<(usize, usize)>> = HashMap::new();
+    for (row, cells) in grid.iter().enumerate() {
+        for (column, &cell) in cells.iter().enumerate() {
+            if cell.is_ascii_lowercase() {
+                rooms.entry(cell).or_default().push((column, row));
+            } else if cell != '#' && cell != '.' && cell != ' ' && door_from_cell(cell).is_none() {
+                return Err(FloorPlanError::UnknownCell { cell, row: row + 1, column: column + 1 });
+            }
+        }
+    }
+    let cell_at = |column: usize, row: usize| grid.get(row).and_then(|cells| cells.get(column)).copied().unwrap_or(' ');
+
+    // Keys are known by the tag of the room they open
+    let mut keys: HashMap<char, Vec<String>> = HashMap::new();
+    for (&room, definition) in &definitions {
+        for &(key_room, line) in &definition.keys {
+            let tag = definitions.get(&key_room).and_then(|definition| definition.tag.clone());
+            keys.entry(room).or_default().push(tag.ok_or(FloorPlanError::InvalidDefinition { line })?);
+        }
+    }
+
+    let mut room_names: Vec<char> = rooms.keys().copied().collect();
+    room_names.sort();
+    let mut claimed_doors = HashSet::new();
+    let mut children = Vec::new();
+    for room in room_names {
+        let cells = &rooms[&room];
+        let left = cells.iter().map(|cell| cell.0).min().unwrap();
+        let right = cells.iter().map(|cell| cell.0).max().unwrap();
+        let top = cells.iter().map(|cell| cell.1).min().unwrap();
+        let bottom = cells.iter().map(|cell| cell.1).max().unwrap();
+        if cells.len() != (right - left + 1) * (bottom - top + 1) {
+            return Err(FloorPlanError::NonRectangularRoom(room));
+        }
+        // Rooms reach to the middle of the walls around them
+        let rect = egui::Rect::from_min_max(
+            Pos2 { x: (left as f32 - 0.5) * scale, y: (top as f32 - 0.5) * scale },
+            Pos2 { x: (right as f32 + 1.5) * scale, y: (bottom as f32 + 1.5) * scale });
+        // Wall cells around the room, by side, with how far along that side their middle is
+        let mut neighbours = Vec::new();
+        for row in top..=bottom {
+            let offset = (row - top + 1) as f32 * scale;
+            neighbours.push((0, Some(right + 1), Some(row), offset));
+            neighbours.push((2, left.checked_sub(1), Some(row), offset));
+        }
+        for column in left..=right {
+            let offset = (column - left + 1) as f32 * scale;
+            neighbours.push((1, Some(column), Some(bottom + 1), offset));
+            neighbours.push((3, Some(column), top.checked_sub(1), offset));
+        }
+        let mut doors = [Vec::new(), Vec::new(), Vec::new(), Vec::new()];
+        let mut door_offsets = [Vec::new(), Vec::new(), Vec::new(), Vec::new()];
+        for (side, column, row, offset) in neighbours {
+            let (Some(column), Some(row)) = (column, row) else {
+                continue;
+            };
+            if let Some(door) = door_from_cell(cell_at(column, row)) {
+                if claimed_doors.insert((column, row)) {
+                    doors[side].push(door);
+                    door_offsets[side].push(offset);
+                }
+            }
+        }
+        let definition = definitions.remove(&room).unwrap_or_default();
+        children.push(BuildingChunk::new(rect, doors, false)
+            .with_door_offsets(door_offsets)
+            .with_kind(definition.kind)
+            .with_shape(definition.shape)
+            .with_level(definition.level)
+            .with_tag(definition.tag)
+            .with_keys(keys.remove(&room).unwrap_or_default()));
+    }
+
+    let bounds = children.iter()
+        .map(|chunk| chunk.rect)
+        .reduce(|bounds, rect| bounds.union(rect))
+        .ok_or(FloorPlanError::NoRooms)?;
+    let mut building = BuildingChunk::new(bounds, [Vec::new(), Vec::new(), Vec::new(), Vec::new()], false);
+    building.divided_chunks = Some(BuildingChunkData::Parent(children, false));
+    Ok(building)
+}
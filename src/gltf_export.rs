@@ -0,0 +1,223 @@
+//! Writes building geometry to glTF, so generated buildings can be dressed in Blender.
+//!
+//! Every room becomes a node named after its tag (or its index), holding one mesh with a
+//! primitive per material. A `.glb` path writes a single binary file, anything else writes
+//! a `.gltf` with its buffer next to it in a `.bin` file.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, VertexAttributeValues};
+use serde_json::{json, Value};
+
+use crate::building::{BuildingBox, BuildingChunk, BuildingChunkData, DOOR_HEIGHT};
+use crate::DOOR_WIDTH;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+
+#[derive(Clone, Copy)]
+enum BuildingMaterial {
+    Wall,
+    Floor,
+    Door,
+    Battlement,
+}
+
+impl BuildingMaterial {
+    const ALL: [BuildingMaterial; 4] = [BuildingMaterial::Wall, BuildingMaterial::Floor, BuildingMaterial::Door, BuildingMaterial::Battlement];
+
+    fn to_json(self) -> Value {
+        let (name, color) = match self {
+            BuildingMaterial::Wall => ("wall", Color::rgb_u8(124, 144, 255)),
+            BuildingMaterial::Floor => ("floor", Color::rgb_u8(90, 80, 70)),
+            BuildingMaterial::Door => ("door", Color::rgb_u8(124, 255, 124)),
+            BuildingMaterial::Battlement => ("battlement", Color::rgb_u8(110, 110, 130)),
+        };
+        json!({
+            "name": name,
+            "pbrMetallicRoughness": {
+                "baseColorFactor": color.as_linear_rgba_f32(),
+                "metallicFactor": 0.0,
+                "roughnessFactor": 0.9,
+            },
+        })
+    }
+}
+
+#[derive(Default)]
+struct Primitive {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    indices: Vec<u32>,
+}
+
+impl Primitive {
+    fn add_mesh(&mut self, mesh: Mesh) {
+        let offset = self.positions.len() as u32;
+        if let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            self.positions.extend(positions);
+        }
+        if let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+            self.normals.extend(normals);
+        }
+        match mesh.indices() {
+            Some(Indices::U16(indices)) => self.indices.extend(indices.iter().map(|index| *index as u32 + offset)),
+            Some(Indices::U32(indices)) => self.indices.extend(indices.iter().map(|index| index + offset)),
+            None => {}
+        }
+    }
+
+    fn add_box(&mut self, building_box: BuildingBox) {
+        self.add_mesh(Mesh::from(Cuboid::from_size(building_box.size)).transformed_by(building_box.transform));
+    }
+}
+
+/// Accumulates the binary buffer and the json objects pointing into it.
+#[derive(Default)]
+struct GltfBuilder {
+    buffer: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl GltfBuilder {
+    fn push_view(&mut self, bytes: &[u8], target: u32) -> usize {
+        self.buffer.resize(self.buffer.len().next_multiple_of(4), 0);
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": self.buffer.len(),
+            "byteLength": bytes.len(),
+            "target": target,
+        }));
+        self.buffer.extend_from_slice(bytes);
+        self.buffer_views.len() - 1
+    }
+
+    fn push_vec3s(&mut self, values: &[[f32; 3]], with_bounds: bool) -> usize {
+        let bytes: Vec<u8> = values.iter().flatten().flat_map(|value| value.to_le_bytes()).collect();
+        let view = self.push_view(&bytes, ARRAY_BUFFER);
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": values.len(),
+            "type": "VEC3",
+        });
+        if with_bounds {
+            let min = values.iter().fold(Vec3::splat(f32::MAX), |min, value| min.min(Vec3::from(*value)));
+            let max = values.iter().fold(Vec3::splat(f32::MIN), |max, value| max.max(Vec3::from(*value)));
+            accessor["min"] = json!(min.to_array());
+            accessor["max"] = json!(max.to_array());
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let bytes: Vec<u8> = indices.iter().flat_map(|index| index.to_le_bytes()).collect();
+        let view = self.push_view(&bytes, ELEMENT_ARRAY_BUFFER);
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": UNSIGNED_INT,
+            "count": indices.len(),
+            "type": "SCALAR",
+        }));
+        self.accessors.len() - 1
+    }
+}
+
+fn room_primitives(chunk: &BuildingChunk, openings: &[Vec3]) -> [Primitive; 4] {
+    let mut primitives: [Primitive; 4] = Default::default();
+    for wall in chunk.wall_boxes(openings) {
+        primitives[BuildingMaterial::Wall as usize].add_box(wall);
+    }
+    primitives[BuildingMaterial::Floor as usize].add_mesh(chunk.floor_mesh());
+    for (.., transform) in chunk.door_transforms() {
+        primitives[BuildingMaterial::Door as usize].add_box(BuildingBox { transform, size: Vec3::new(0.11, DOOR_HEIGHT, DOOR_WIDTH) });
+    }
+    for merlon in chunk.merlon_boxes() {
+        primitives[BuildingMaterial::Battlement as usize].add_box(merlon);
+    }
+    primitives
+}
+
+/// Builds the glTF json for a building, along with the contents of its single buffer.
+fn building_to_gltf(building: &BuildingChunk, buffer_uri: Option<&str>) -> (Value, Vec<u8>) {
+    let mut builder = GltfBuilder::default();
+    let mut meshes = Vec::new();
+    let mut nodes = Vec::new();
+    let openings = building.door_openings();
+    let rooms = building.get_all().into_iter()
+        .filter(|chunk| !matches!(chunk.divided_chunks, Some(BuildingChunkData::Parent(..))));
+    for (room_index, chunk) in rooms.enumerate() {
+        let name = chunk.tag.clone().unwrap_or_else(|| format!("room_{room_index}"));
+        let mut primitives = Vec::new();
+        for (material, primitive) in room_primitives(chunk, &openings).into_iter().enumerate() {
+            if primitive.indices.is_empty() {
+                continue;
+            }
+            let position = builder.push_vec3s(&primitive.positions, true);
+            let normal = builder.push_vec3s(&primitive.normals, false);
+            let indices = builder.push_indices(&primitive.indices);
+            primitives.push(json!({
+                "attributes": { "POSITION": position, "NORMAL": normal },
+                "indices": indices,
+                "material": material,
+            }));
+        }
+        nodes.push(json!({ "name": name, "mesh": meshes.len() }));
+        meshes.push(json!({ "name": name, "primitives": primitives }));
+    }
+    let mut buffer = json!({ "byteLength": builder.buffer.len() });
+    if let Some(uri) = buffer_uri {
+        buffer["uri"] = json!(uri);
+    }
+    let gltf = json!({
+        "asset": { "version": "2.0", "generator": "dracula_game building exporter" },
+        "scene": 0,
+        "scenes": [{ "name": "building", "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+        "nodes": nodes,
+        "meshes": meshes,
+        "materials": BuildingMaterial::ALL.map(BuildingMaterial::to_json),
+        "accessors": builder.accessors,
+        "bufferViews": builder.buffer_views,
+        "buffers": [buffer],
+    });
+    (gltf, builder.buffer)
+}
+
+fn padded(mut bytes: Vec<u8>, padding: u8) -> Vec<u8> {
+    bytes.resize(bytes.len().next_multiple_of(4), padding);
+    bytes
+}
+
+/// Exports a building to `path`, as `.glb` or `.gltf` depending on its extension.
+pub fn export_building(building: &BuildingChunk, path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("glb")) {
+        let (gltf, buffer) = building_to_gltf(building, None);
+        let json_chunk = padded(serde_json::to_vec(&gltf)?, b' ');
+        let binary_chunk = padded(buffer, 0);
+        let mut glb = Vec::new();
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&((12 + 8 + json_chunk.len() + 8 + binary_chunk.len()) as u32).to_le_bytes());
+        glb.extend_from_slice(&(json_chunk.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json_chunk);
+        glb.extend_from_slice(&(binary_chunk.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&binary_chunk);
+        fs::write(path, glb)
+    } else {
+        let buffer_path = path.with_extension("bin");
+        let buffer_uri = buffer_path.file_name().and_then(|name| name.to_str()).unwrap_or("building.bin");
+        let (gltf, buffer) = building_to_gltf(building, Some(buffer_uri));
+        fs::write(&buffer_path, buffer)?;
+        fs::write(path, serde_json::to_string_pretty(&gltf)?)
+    }
+}
@@ -0,0 +1,204 @@
+use bevy::ecs::component::{SparseStorage, TableStorage};
+use bevy::ecs::system::{StaticSystemParam, SystemParam, SystemParamItem};
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy_egui::{egui, EguiContexts};
+use bevy_persistent::Persistent;
+use bevy_trait_query::RegisterExt;
+use bevy_xpbd_3d::prelude::ColliderAabb;
+
+use crate::kinematic_character_3d::CharacterController;
+use crate::states::MyAppState;
+use crate::Settings;
+
+/// How far away an interactable can be selected from, and stays open until.
+pub const INTERACTION_RANGE: f32 = 5.0;
+/// Sent as an [`InteractableAction`] when interact is pressed on something that
+/// [acts on it](EguiInteractableEmpty::acts_on_interact) rather than opening its window.
+pub const INTERACT_ACTION: &str = "interact";
+
+/// Marker component added to an interactable while its egui window is open.
+pub trait EguiInteractableComponent: Component<Storage=SparseStorage> {
+    type Param: SystemParam + 'static;
+    fn system(param: SystemParamItem<Self::Param>);
+}
+
+/// Component put on anything that can be selected, building its [`EguiInteractableComponent`] once it is.
+pub trait EguiInteractableEmpty: Component<Storage=TableStorage> {
+    type Interaction: EguiInteractableComponent;
+    fn build_interactable_component(&self) -> Self::Interaction;
+    /// The open flag handed to the egui window, reset once the window has been closed.
+    fn window_open(&mut self) -> &mut bool;
+    fn name(&self) -> String;
+    /// Actions that can be applied to many of these at once, sent as [`InteractableAction`]s.
+    fn batch_actions(&self) -> &'static [&'static str] {
+        &[]
+    }
+    /// What pressing interact does, shown under the crosshair.
+    fn prompt(&self) -> String {
+        format!("Use {}", self.name())
+    }
+    /// Whether pressing interact sends [`INTERACT_ACTION`] instead of opening the window.
+    fn acts_on_interact(&self) -> bool {
+        false
+    }
+}
+
+/// Type erased view of every [`EguiInteractableEmpty`], for selection code that works on any of them.
+#[bevy_trait_query::queryable]
+pub trait Interactable {
+    fn select(&self, entity: Entity, commands: &mut Commands);
+    fn name(&self) -> String;
+    fn batch_actions(&self) -> &'static [&'static str];
+    fn prompt(&self) -> String;
+    fn acts_on_interact(&self) -> bool;
+}
+
+impl<T: EguiInteractableEmpty> Interactable for T {
+    fn select(&self, entity: Entity, commands: &mut Commands) {
+        commands.entity(entity).insert(self.build_interactable_component());
+    }
+    fn name(&self) -> String {
+        EguiInteractableEmpty::name(self)
+    }
+    fn batch_actions(&self) -> &'static [&'static str] {
+        EguiInteractableEmpty::batch_actions(self)
+    }
+    fn prompt(&self) -> String {
+        EguiInteractableEmpty::prompt(self)
+    }
+    fn acts_on_interact(&self) -> bool {
+        EguiInteractableEmpty::acts_on_interact(self)
+    }
+}
+
+/// What pressing interact does right now, for interactables whose prompt depends on more than their
+/// [`EguiInteractableEmpty`]. Shown in place of its [`prompt`](EguiInteractableEmpty::prompt).
+#[derive(Component, Debug, Default, Clone, PartialEq, Eq)]
+pub struct InteractPrompt(pub String);
+
+/// Every interactable caught by the last box selection.
+#[derive(Resource, Default, Debug)]
+pub struct Selection(pub HashSet<Entity>);
+
+/// A batch action picked from the selection window, sent once for each selected entity offering it.
+#[derive(Event, Debug, Clone)]
+pub struct InteractableAction {
+    pub entity: Entity,
+    pub action: &'static str,
+}
+
+pub struct InteractablePlugin;
+
+impl Plugin for InteractablePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Selection>()
+            .add_event::<InteractableAction>()
+            .add_systems(Update, (selection_window, highlight_selection)
+                .in_set(InteractableSet)
+                .run_if(in_state(MyAppState::InGame)));
+    }
+}
+
+/// Systems that run interactable windows, after selection has happened for the frame.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InteractableSet;
+
+pub trait InteractableAppExt {
+    /// Makes `T` selectable and shows its egui window while selected.
+    fn register_interactable<T: EguiInteractableEmpty>(&mut self) -> &mut Self;
+}
+
+impl InteractableAppExt for App {
+    fn register_interactable<T: EguiInteractableEmpty>(&mut self) -> &mut Self {
+        self.register_component_as::<dyn Interactable, T>()
+            .add_systems(
+                Update,
+                (
+                    close_interactables::<T>,
+                    run_interaction::<T::Interaction>.run_if(any_with_component::<T::Interaction>),
+                ).chain()
+                    .in_set(InteractableSet)
+                    .run_if(in_state(MyAppState::InGame)),
+            )
+    }
+}
+
+fn close_interactables<T: EguiInteractableEmpty>(mut commands: Commands,
+                                                 q_player: Query<&GlobalTransform, With<CharacterController>>,
+                                                 mut q_interactables: Query<(Entity, &GlobalTransform, &mut T), With<T::Interaction>>) {
+    let Ok(player_transform) = q_player.get_single() else {
+        return;
+    };
+    for (entity, transform, mut interactable) in &mut q_interactables {
+        if player_transform.translation().distance(transform.translation()) > INTERACTION_RANGE {
+            commands.entity(entity).remove::<T::Interaction>();
+        }
+        if !*interactable.window_open() {
+            commands.entity(entity).remove::<T::Interaction>();
+            *interactable.window_open() = true;
+        }
+    }
+}
+
+fn run_interaction<C: EguiInteractableComponent>(param: StaticSystemParam<C::Param>) {
+    C::system(param.into_inner());
+}
+
+fn selection_window(mut commands: Commands,
+                    mut contexts: EguiContexts,
+                    mut selection: ResMut<Selection>,
+                    q_interactables: Query<&dyn Interactable>,
+                    mut actions: EventWriter<InteractableAction>) {
+    selection.0.retain(|entity| q_interactables.contains(*entity));
+    if selection.0.len() < 2 {
+        return;
+    }
+    let mut open = true;
+    egui::Window::new("Selection").open(&mut open).show(contexts.ctx_mut(), |ui| {
+        let mut available: Vec<&'static str> = Vec::new();
+        for interactable in selection.0.iter().filter_map(|entity| q_interactables.get(*entity).ok()).flatten() {
+            ui.label(interactable.name());
+            for action in interactable.batch_actions() {
+                if !available.contains(action) {
+                    available.push(action);
+                }
+            }
+        }
+        ui.horizontal(|ui| {
+            if ui.button("inspect").clicked() {
+                for &entity in &selection.0 {
+                    for interactable in q_interactables.get(entity).into_iter().flatten() {
+                        interactable.select(entity, &mut commands);
+                    }
+                }
+            }
+            for action in available {
+                if ui.button(action).clicked() {
+                    for &entity in &selection.0 {
+                        if q_interactables.get(entity).into_iter().flatten().any(|interactable| interactable.batch_actions().contains(&action)) {
+                            actions.send(InteractableAction { entity, action });
+                        }
+                    }
+                }
+            }
+        });
+    });
+    if !open {
+        selection.0.clear();
+    }
+}
+
+fn highlight_selection(selection: Res<Selection>,
+                       settings: Res<Persistent<Settings>>,
+                       q_aabb: Query<&ColliderAabb>,
+                       mut gizmos: Gizmos) {
+    for aabb in selection.0.iter().filter_map(|entity| q_aabb.get(*entity).ok()) {
+        highlight(&mut gizmos, aabb, settings.accessibility.palette.selection());
+    }
+}
+
+/// Outlines an interactable with a box around its collider.
+pub fn highlight(gizmos: &mut Gizmos, aabb: &ColliderAabb, color: Color) {
+    gizmos.cuboid(Transform::from_translation((aabb.min + aabb.max) / 2.0).with_scale(aabb.max - aabb.min), color);
+}
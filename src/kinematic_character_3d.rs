@@ -1,19 +1,35 @@
+use std::f32::consts::PI;
 use std::ops::{Add, Div, Mul};
+use std::time::Duration;
 use bevy::{ecs::query::Has, prelude::*};
 use bevy::window::{CursorGrabMode, PrimaryWindow};
 use bevy_ecs::component::{SparseStorage, TableStorage};
-use bevy_ecs::system::SystemParam;
+use bevy_ecs::system::{SystemParam, SystemParamItem};
 use bevy_egui::{egui, EguiContexts};
 use bevy_egui::egui::{Color32, Id, Pos2};
 use bevy_persistent::Persistent;
-use bevy_xpbd_3d::{math::*, prelude::*, SubstepSchedule, SubstepSet};
+use bevy_xpbd_3d::{math::*, prelude::*, PhysicsSchedule, PhysicsStepSet, SubstepSchedule, SubstepSet};
 use bevy_xpbd_3d::parry::na::DimMul;
 use leafwing_input_manager::orientation::Orientation;
 use leafwing_input_manager::prelude::InputKind::Mouse;
 use leafwing_input_manager::prelude::*;
-use crate::building::DoorEnum;
-use crate::{PlayerMarker, Settings};
+use crate::building::{DoorEnum, STOREY_HEIGHT};
+use crate::collide_and_slide::{collide_and_slide, step_up, SlideHit};
+use crate::{DayTimer, Settings};
+use crate::controls::{apply_deadzone, controls_ui, input_label, RebindInput, Rebinding};
+use crate::locomotion::{accelerate, MovementSettings};
+use crate::player_camera::{CameraMode, PlayerCamera};
+use crate::accessibility::{accessibility_ui, UIToggleMode};
+use crate::settings::{save_settings, settings_status_ui, SettingsStatus};
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+use crate::door::DoorState;
+use crate::interactable::{EguiInteractableComponent, EguiInteractableEmpty, INTERACT_ACTION, Interactable, InteractableAction, InteractableAppExt, InteractableSet, INTERACTION_RANGE, InteractPrompt, Selection, highlight};
+use crate::door::{DOOR_CLOSE_ACTION, DOOR_OPEN_ACTION};
+use crate::lock::{DoorLock, Key, LockPicking, LockSave};
 use crate::states::{AppCursorState, MyAppState};
+use crate::simulation::GameCommand;
+use crate::replay::ReplaySet;
 
 #[derive(PhysicsLayer, Clone, Copy, Debug)]
 pub enum GameLayer {
@@ -23,55 +39,73 @@ pub enum GameLayer {
     RaycastInteractible,
 }
 
-pub trait EguiInteractableComponent: Component<Storage=SparseStorage> {
-    type Param<'a, 'b>: SystemParam;
-    fn system(param: Self::Param<'_, '_>);
-}
-
-pub trait EguiInteractableEmpty<EguiInteractableComponent: Component>: Component<Storage=TableStorage> {
-    fn build_interactable_component(&self) -> EguiInteractableComponent;
-}
-
 #[derive(Component)]
 #[component(storage = "SparseSet")]
 pub struct DoorEguiInteractableComponent;
+type DoorWindowQuery<'w, 's> = Query<'w, 's, (Entity, &'static mut DoorEguiInteractableEmpty, &'static GlobalTransform, &'static DoorLock, &'static mut DoorState, Option<&'static LockPicking>),
+                                       (Without<Camera>, Without<CharacterController>, With<DoorEguiInteractableComponent>)>;
 #[derive(SystemParam)]
 pub struct DoorEguiInteractionParam<'w, 's> {
+    commands: Commands<'w, 's>,
     egui_contexts: EguiContexts<'w, 's>,
-    q_door_egui_component: Query<'w, 's, (&'static mut DoorEguiInteractableEmpty,&'static mut Transform),(Without<Camera>, Without<CharacterController>,With<DoorEguiInteractableComponent>)>,
-    q_player_transform: Query<'w, 's, &'static mut Transform, (With<Camera>, With<CharacterController>)>,
+    lock_save: Res<'w, Persistent<LockSave>>,
+    q_door_egui_component: DoorWindowQuery<'w, 's>,
+    q_player_transform: Query<'w, 's, &'static mut Transform, With<CharacterController>>,
 }
 
 impl EguiInteractableComponent for DoorEguiInteractableComponent {
-    type Param<'a, 'b> = DoorEguiInteractionParam<'a, 'b>;
-    fn system(mut param: Self::Param<'_, '_>) {
-        param.q_door_egui_component.iter_mut().for_each(|mut door_egui_interactable_component| {
-            let id = door_egui_interactable_component.0.id.to_string();
-            let door_enum = door_egui_interactable_component.0.door_enum;
-            egui::Window::new("Door").id(Id::new(id)).open(&mut door_egui_interactable_component.0.window_open).show(param.egui_contexts.ctx_mut(), |ui| {
-                if let DoorEnum::Interior(locked ) = door_enum{
-                    ui.label("This door is locked.");
-                }else {
-                    if ui.button("enter").clicked(){
-                    if(door_egui_interactable_component.1.rotation.y / (std::f32::consts::PI * 2.0) % 2.0 == 1.0){
-                        let diff = param.q_player_transform.get_single_mut().unwrap().translation.x - door_egui_interactable_component.1.translation.x;
-                        if diff > 0.0{
-                            param.q_player_transform.get_single_mut().unwrap().translation.x -= (diff+1.0);
-                        }else {
-                            param.q_player_transform.get_single_mut().unwrap().translation.x -= (diff-1.0);
+    type Param = DoorEguiInteractionParam<'static, 'static>;
+    fn system(mut param: SystemParamItem<Self::Param>) {
+        // Apps can run without a player body, and then there's nobody to show the windows to
+        let Ok(player) = param.q_player_transform.get_single() else {
+            return;
+        };
+        let player_position = player.translation;
+        param.q_door_egui_component.iter_mut().for_each(|(entity, mut door, door_transform, lock, mut state, picking)| {
+            let id = door.id.to_string();
+            let door_enum = door.door_enum;
+            let player_inside = lock.is_inside(door_transform.translation(), player_position);
+            let key = param.lock_save.key_for(lock).map(|key| key.name.clone());
+            egui::Window::new("Door").id(Id::new(id)).open(&mut door.window_open).show(param.egui_contexts.ctx_mut(), |ui| {
+                if let DoorEnum::Stairs(level) = door_enum{
+                    if ui.button(if level < 0 {"descend"} else {"climb"}).clicked(){
+                        if let Ok(mut player) = param.q_player_transform.get_single_mut() {
+                            climb_stairs(level, door_transform.translation(), &mut player);
+                        }
+                    }
+                    return;
+                }
+                match *state{
+                    DoorState::Locked => {
+                        ui.label("This door is locked.");
+                        if player_inside{
+                            if ui.button("unlock").clicked(){
+                                *state = DoorState::Closed;
+                            }
+                        }else if let Some(key) = key{
+                            if ui.button(format!("unlock with {key}")).clicked(){
+                                *state = DoorState::Closed;
+                            }
+                        }else if let Some(picking) = picking{
+                            ui.add(egui::ProgressBar::new(picking.0.fraction()).text("picking the lock..."));
+                        }else if ui.button("pick the lock").clicked(){
+                            param.commands.entity(entity).insert(LockPicking::default());
                         }
-                        param.q_player_transform.get_single_mut().unwrap().translation.z = door_egui_interactable_component.1.translation.z;
-                    }else{
-                        let diff = param.q_player_transform.get_single_mut().unwrap().translation.z - door_egui_interactable_component.1.translation.z;
-                        if diff > 0.0{
-                            param.q_player_transform.get_single_mut().unwrap().translation.z -= (diff+1.0);
-                        }else {
-                            param.q_player_transform.get_single_mut().unwrap().translation.z -= (diff-1.0);
+                    }
+                    DoorState::Closed => {
+                        if ui.button("open").clicked(){
+                            *state = DoorState::Open;
+                        }
+                        if (player_inside || key.is_some()) && ui.button("lock").clicked(){
+                            *state = DoorState::Locked;
+                        }
+                    }
+                    DoorState::Open => {
+                        if ui.button("close").clicked(){
+                            *state = DoorState::Closed;
                         }
-                        param.q_player_transform.get_single_mut().unwrap().translation.x = door_egui_interactable_component.1.translation.x;
                     }
                 }
-            }
             });
         });
     }
@@ -85,40 +119,181 @@ pub struct DoorEguiInteractableEmpty {
 }
 
 
-impl EguiInteractableEmpty<DoorEguiInteractableComponent> for DoorEguiInteractableEmpty {
+impl EguiInteractableEmpty for DoorEguiInteractableEmpty {
+    type Interaction = DoorEguiInteractableComponent;
     fn build_interactable_component(&self) -> DoorEguiInteractableComponent {
         DoorEguiInteractableComponent {}
     }
+    fn window_open(&mut self) -> &mut bool {
+        &mut self.window_open
+    }
+    fn name(&self) -> String {
+        match self.door_enum {
+            DoorEnum::Exterior => "Front door".to_string(),
+            DoorEnum::Stairs(_) => "Stairs".to_string(),
+            _ => "Door".to_string(),
+        }
+    }
+    fn batch_actions(&self) -> &'static [&'static str] {
+        // Stairs are only ever climbed
+        match self.door_enum {
+            DoorEnum::Stairs(_) => &[],
+            _ => &[DOOR_OPEN_ACTION, DOOR_CLOSE_ACTION],
+        }
+    }
+    fn acts_on_interact(&self) -> bool {
+        true
+    }
+}
+
+/// Moves the player through stairs leading to storey `level`, keeping their height above the floor.
+fn climb_stairs(level: i32, door: Vec3, player: &mut Transform) {
+    let door_floor = door.y - STOREY_HEIGHT / 2.0;
+    player.translation.y += level as f32 * STOREY_HEIGHT - door_floor;
+}
+
+/// What pressing interact on a door does as things stand, the same as the first button in its window.
+enum DoorInteraction {
+    Climb(i32),
+    Open,
+    Close,
+    /// Unlocking from the inside, or with the named key.
+    Unlock(Option<String>),
+    PickLock,
+}
+
+impl DoorInteraction {
+    fn new(door_enum: DoorEnum, state: DoorState, key: Option<&Key>, player_inside: bool) -> Self {
+        match (door_enum, state) {
+            (DoorEnum::Stairs(level), _) => DoorInteraction::Climb(level),
+            (_, DoorState::Open) => DoorInteraction::Close,
+            (_, DoorState::Closed) => DoorInteraction::Open,
+            (_, DoorState::Locked) if player_inside => DoorInteraction::Unlock(None),
+            (_, DoorState::Locked) => match key {
+                Some(key) => DoorInteraction::Unlock(Some(key.name.clone())),
+                None => DoorInteraction::PickLock,
+            },
+        }
+    }
+
+    fn prompt(&self, door_enum: DoorEnum) -> String {
+        let door = if let DoorEnum::Exterior = door_enum { "front door" } else { "door" };
+        match self {
+            DoorInteraction::Climb(level) if *level < 0 => "Go downstairs".to_string(),
+            DoorInteraction::Climb(_) => "Go upstairs".to_string(),
+            DoorInteraction::Open => format!("Open {door}"),
+            DoorInteraction::Close => format!("Close {door}"),
+            DoorInteraction::Unlock(None) => format!("Unlock {door}"),
+            DoorInteraction::Unlock(Some(key)) => format!("Unlock with {key}"),
+            DoorInteraction::PickLock => "Pick the lock".to_string(),
+        }
+    }
+}
+
+type DoorInteractionQuery<'w, 's> = Query<'w, 's, (&'static DoorEguiInteractableEmpty, &'static GlobalTransform, &'static DoorLock, &'static mut DoorState,
+                                                   Has<LockPicking>), Without<CharacterController>>;
+
+/// Keeps each door's [`InteractPrompt`] in step with what interact would do to it.
+fn door_prompts(lock_save: Res<Persistent<LockSave>>,
+                q_player: Query<&GlobalTransform, With<CharacterController>>,
+                mut q_doors: Query<(&DoorEguiInteractableEmpty, &GlobalTransform, &DoorLock, &DoorState, &mut InteractPrompt)>) {
+    let Ok(player) = q_player.get_single() else {
+        return;
+    };
+    for (door, door_transform, lock, state, mut prompt) in &mut q_doors {
+        let player_inside = lock.is_inside(door_transform.translation(), player.translation());
+        let interaction = DoorInteraction::new(door.door_enum, *state, lock_save.key_for(lock), player_inside);
+        prompt.set_if_neq(InteractPrompt(interaction.prompt(door.door_enum)));
+    }
+}
+
+/// Does whatever interact does to the door it was pressed on. Locks that need picking open the door's
+/// window to show the attempt, which is given up when the window closes.
+fn interact_with_doors(mut commands: Commands,
+                       mut actions: EventReader<InteractableAction>,
+                       lock_save: Res<Persistent<LockSave>>,
+                       mut q_doors: DoorInteractionQuery,
+                       mut q_player: Query<&mut Transform, With<CharacterController>>) {
+    for action in actions.read().filter(|action| action.action == INTERACT_ACTION) {
+        let Ok((door, door_transform, lock, mut state, picking)) = q_doors.get_mut(action.entity) else {
+            continue;
+        };
+        let Ok(mut player) = q_player.get_single_mut() else {
+            return;
+        };
+        let player_inside = lock.is_inside(door_transform.translation(), player.translation);
+        match DoorInteraction::new(door.door_enum, *state, lock_save.key_for(lock), player_inside) {
+            DoorInteraction::Climb(level) => climb_stairs(level, door_transform.translation(), &mut player),
+            DoorInteraction::Open => *state = DoorState::Open,
+            DoorInteraction::Close => *state = DoorState::Closed,
+            DoorInteraction::Unlock(_) => *state = DoorState::Closed,
+            DoorInteraction::PickLock if !picking => {
+                commands.entity(action.entity).insert((door.build_interactable_component(), LockPicking::default()));
+            }
+            DoorInteraction::PickLock => {}
+        }
+    }
 }
 #[derive(Debug, Default, Component)]
 pub struct VirtualPointer {
     pub start_click_pos: Option<Vec2>,
     pub current_pos: Option<Vec2>,
+    /// Pixels per second the pointer moves at under the right stick, building up while the stick is held.
+    pub stick_speed: f32,
 }
 pub struct CharacterControllerPlugin;
-#[derive(Actionlike, PartialEq, Eq, Hash, Clone, Copy, Debug, Reflect)]
+#[derive(Actionlike, PartialEq, Eq, Hash, Clone, Copy, Debug, Reflect, Serialize, Deserialize, EnumIter, strum_macros::Display)]
 pub enum PlayerMovement {
     Look,
+    /// Looking, or moving the virtual pointer, with a gamepad stick.
+    StickLook,
     Move,
     Click,
     UIToggle,
     Pause,
+    Interact,
+    Jump,
+    Sprint,
+    Crouch,
+    /// Steps through first person, third person and top down.
+    CycleCamera,
 }
-impl Plugin for CharacterControllerPlugin {
+/// Moves the player's body from their input, and nothing more, so it runs without a window.
+pub struct CharacterMovementPlugin;
+
+impl Plugin for CharacterMovementPlugin {
     fn build(&self, app: &mut App) {
         app
+            .add_plugins(InputManagerPlugin::<PlayerMovement>::default())
+            .insert_resource(Time::<Physics>::from_timestep(TimestepMode::Fixed {
+                delta: PHYSICS_TICK,
+                overstep: Duration::ZERO,
+                max_delta_overstep: PHYSICS_CATCH_UP,
+            }))
             .add_systems(
                 Update,
                 (
-                    movement,
-                    apply_movement_damping,
-                    mesh_snip_select_system,
-                    DoorEguiInteractableComponent::system.run_if(any_with_component::<DoorEguiInteractableComponent>)
+                    respawn_player,
+                    update_grounded,
+                    crouch,
+                    steer,
                 ).run_if(in_state(MyAppState::InGame))
                     .chain(),
-            ).add_systems(Update,paused_update.run_if(in_state(MyAppState::Paused)))
-            .add_systems(OnEnter(MyAppState::LoadingScreen),load)
-            .add_plugins(InputManagerPlugin::<PlayerMovement>::default())
+            )
+            .add_systems(
+                // Velocity is integrated on a fixed tick so movement feels the same at any frame rate
+                FixedUpdate,
+                (apply_gravity, apply_movement).run_if(in_state(MyAppState::InGame)),
+            )
+            // Recordings only hold the frames the player can move on, so loading takes as long as it likes
+            .configure_sets(PreUpdate, ReplaySet.run_if(in_state(MyAppState::InGame).or_else(entering_game)))
+            .add_systems(
+                PhysicsSchedule,
+                (
+                    slide_controllers.before(PhysicsStepSet::BroadPhase),
+                    restore_slide_velocity.after(PhysicsStepSet::SpatialQuery),
+                ),
+            )
             .add_systems(
                 // Run collision handling in substep schedule
                 SubstepSchedule,
@@ -126,13 +301,42 @@ impl Plugin for CharacterControllerPlugin {
             );
     }
 }
+
+/// Whether the game is entered this frame. The state only changes after [`PreUpdate`], but the
+/// player moves on this frame all the same.
+fn entering_game(next_state: Option<Res<NextState<MyAppState>>>) -> bool {
+    next_state.is_some_and(|next_state| next_state.0 == Some(MyAppState::InGame))
+}
+
+impl Plugin for CharacterControllerPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(CharacterMovementPlugin)
+            .add_systems(
+                Update,
+                (
+                    movement,
+                    door_prompts,
+                    mesh_snip_select_system,
+                    interact_with_doors,
+                ).run_if(in_state(MyAppState::InGame))
+                    .chain()
+                    .after(steer),
+            )
+            .configure_sets(Update, InteractableSet.after(mesh_snip_select_system))
+            .register_interactable::<DoorEguiInteractableEmpty>().add_systems(Update,paused_update.run_if(in_state(MyAppState::Paused)))
+            .add_systems(OnEnter(MyAppState::LoadingScreen),load)
+            .add_systems(OnEnter(MyAppState::Paused), free_cursor)
+            .add_systems(OnExit(MyAppState::Paused), lock_cursor);
+    }
+}
 fn load(mut commands: Commands,
          mut q_windows: Query<&mut Window, With<PrimaryWindow>>){
     let mut primary_window = q_windows.single_mut();
     primary_window.cursor.grab_mode = CursorGrabMode::Confined;
     primary_window.cursor.visible = false;
     commands.spawn((
-        VirtualPointer { start_click_pos: None, current_pos: Some(Vec2 { x: primary_window.width() * 0.5, y: primary_window.height() * 0.5 }) },
+        VirtualPointer { start_click_pos: None, current_pos: Some(Vec2 { x: primary_window.width() * 0.5, y: primary_window.height() * 0.5 }), ..default() },
     ));
 }
 /// An event sent for a movement input action
@@ -141,17 +345,87 @@ fn load(mut commands: Commands,
 #[derive(Component)]
 pub struct CharacterController;
 
+/// Up and down look angle of the player's camera. The camera is a separate entity following the
+/// character, so only it tilts, and the physics capsule stays upright.
+#[derive(Component, Debug)]
+pub struct CameraPitch {
+    pub angle: f32,
+    /// Furthest the camera can look up or down, in radians.
+    pub limit: f32,
+}
+
+impl Default for CameraPitch {
+    fn default() -> Self {
+        Self { angle: 0.0, limit: MAX_PITCH }
+    }
+}
+
+/// Sprinting drains this, and it recovers while the player isn't sprinting.
+#[derive(Component, Debug)]
+pub struct Stamina {
+    pub current: Scalar,
+    pub max: Scalar,
+}
+
+impl Default for Stamina {
+    fn default() -> Self {
+        Self { current: 100.0, max: 100.0 }
+    }
+}
+
+impl Stamina {
+    pub fn fraction(&self) -> f32 {
+        self.current / self.max
+    }
+}
+
+/// The colliders a character swaps between when crouching, a smaller one lets it fit under furniture.
+#[derive(Component)]
+pub struct Crouch {
+    pub crouched: bool,
+    standing: Collider,
+    crouching: Collider,
+}
+
+impl Crouch {
+    pub fn new(standing: Collider, crouching: Collider) -> Self {
+        Self { crouched: false, standing, crouching }
+    }
+}
+
 /// A marker component indicating that an entity is on the ground.
 #[derive(Component)]
 #[component(storage = "SparseSet")]
 pub struct Grounded;
-/// The acceleration used for character movement.
+/// Where the player is trying to go, read from input every frame and acted on every fixed tick.
+#[derive(Component, Default)]
+pub struct MovementIntent {
+    /// Horizontal direction in world space, no longer than one.
+    wish: Vector,
+    sprint: bool,
+}
+
+/// The gravitational acceleration used for a character controller.
 #[derive(Component)]
-pub struct MovementAcceleration(Scalar);
+pub struct ControllerGravity(Vector);
 
-/// The damping factor used for slowing down movement.
+/// The strength of a jump.
 #[derive(Component)]
-pub struct MovementDampingFactor(Scalar);
+pub struct JumpImpulse(Scalar);
+
+/// The maximum angle a slope can have for a character controller
+/// to be able to climb and jump. If the slope is steeper than this angle,
+/// the character will slide down.
+#[derive(Component)]
+pub struct MaxSlopeAngle(Scalar);
+
+/// Ledges up to this height above the character's feet are stepped onto instead of blocking it.
+#[derive(Component)]
+pub struct MaxStepHeight(Scalar);
+
+/// The character's velocity while [`slide_controllers`] has taken over moving it for a physics step.
+#[derive(Component, Default)]
+pub struct SlideVelocity(Vector);
 
 /// A bundle that contains the components needed for a basic
 /// kinematic character controller.
@@ -160,6 +434,10 @@ pub struct CharacterControllerBundle {
     character_controller: CharacterController,
     rigid_body: RigidBody,
     collider: Collider,
+    ground_caster: ShapeCaster,
+    gravity: ControllerGravity,
+    step_height: MaxStepHeight,
+    slide_velocity: SlideVelocity,
     movement: MovementBundle,
     collision_layers: CollisionLayers,
     input_manager:  InputManagerBundle<PlayerMovement>
@@ -168,25 +446,30 @@ pub struct CharacterControllerBundle {
 /// A bundle that contains components for character movement.
 #[derive(Bundle)]
 pub struct MovementBundle {
-    acceleration: MovementAcceleration,
-    damping: MovementDampingFactor,
+    settings: MovementSettings,
+    intent: MovementIntent,
+    jump_impulse: JumpImpulse,
+    max_slope_angle: MaxSlopeAngle,
 }
 
 impl MovementBundle {
-    pub const fn new(
-        acceleration: Scalar,
-        damping: Scalar,
+    pub fn new(
+        settings: MovementSettings,
+        jump_impulse: Scalar,
+        max_slope_angle: Scalar,
     ) -> Self {
         Self {
-            acceleration: MovementAcceleration(acceleration),
-            damping: MovementDampingFactor(damping),
+            settings,
+            intent: MovementIntent::default(),
+            jump_impulse: JumpImpulse(jump_impulse),
+            max_slope_angle: MaxSlopeAngle(max_slope_angle),
         }
     }
 }
 
 impl Default for MovementBundle {
     fn default() -> Self {
-        Self::new(30.0, 0.9)
+        Self::new(MovementSettings::default(), 4.0, PI * 0.25)
     }
 }
 
@@ -199,6 +482,11 @@ impl CharacterControllerBundle {
             character_controller: CharacterController,
             rigid_body: RigidBody::Kinematic,
             collider,
+            ground_caster: ShapeCaster::new(caster_shape, Vector::ZERO, Quaternion::default(), Direction3d::NEG_Y)
+                .with_max_time_of_impact(0.2),
+            gravity: ControllerGravity(Vector::NEG_Y * GRAVITY),
+            step_height: MaxStepHeight(MAX_STEP_HEIGHT),
+            slide_velocity: SlideVelocity::default(),
             collision_layers:CollisionLayers::new(GameLayer::Player, [GameLayer::Environment, GameLayer::Player,GameLayer::RaycastInteractible]),
             movement: MovementBundle::default(),
             input_manager,
@@ -207,90 +495,310 @@ impl CharacterControllerBundle {
 
     pub fn with_movement(
         mut self,
-        acceleration: Scalar,
-        damping: Scalar,
+        settings: MovementSettings,
+        jump_impulse: Scalar,
+        max_slope_angle: Scalar,
     ) -> Self {
-        self.movement = MovementBundle::new(acceleration, damping);
+        self.movement = MovementBundle::new(settings, jump_impulse, max_slope_angle);
         self
     }
 }
 
+/// Pausing, the UI toggle and the pointer, everything about the player's input that needs a window.
+#[allow(clippy::too_many_arguments)]
 fn movement(mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
-            mut contexts: EguiContexts,
-            mut next_app_state: ResMut<NextState<MyAppState>>,
+            mut game_commands: EventWriter<GameCommand>,
             mut next_cursor_state: ResMut<NextState<AppCursorState>>,
-            // state:Res<State<MyAppState>>,
             mut q_pointer: Query<&mut VirtualPointer, With<VirtualPointer>>,
             cursor_state: Res<State<AppCursorState>>,
             time: Res<Time>,
             settings: Res<Persistent<Settings>>,
-    mut controllers: Query<(
-        &ActionState<PlayerMovement>,
-        &MovementAcceleration,
-        &mut LinearVelocity,
-        &mut Rotation,
-        &mut Transform,
-    )>,
+            controllers: Query<&ActionState<PlayerMovement>>,
 ) {
-    for ( mut action_state, movement_acceleration, mut linear_velocity, mut rotation,mut transform) in
-    &mut controllers
-    {
+    for action_state in &controllers {
         let mut primary_window = q_windows.single_mut();
         let mut pointer = q_pointer.get_single_mut().unwrap();
         if action_state.just_pressed(&PlayerMovement::Pause) {
-            next_app_state.set(MyAppState::Paused);
-            primary_window.cursor.grab_mode = CursorGrabMode::None;
-            next_cursor_state.set(AppCursorState::Free);
-            pointer.start_click_pos = None;
+            game_commands.send(GameCommand::Pause);
         }
-        if action_state.just_pressed(&PlayerMovement::UIToggle) {
+        let ui_toggle = settings.accessibility.ui_toggle;
+        let show_pointer = match ui_toggle {
+            UIToggleMode::Hold => action_state.just_pressed(&PlayerMovement::UIToggle),
+            UIToggleMode::Toggle => action_state.just_pressed(&PlayerMovement::UIToggle) && cursor_state.get() == &AppCursorState::Locked,
+        };
+        let hide_pointer = match ui_toggle {
+            UIToggleMode::Hold => action_state.just_released(&PlayerMovement::UIToggle),
+            UIToggleMode::Toggle => action_state.just_pressed(&PlayerMovement::UIToggle) && cursor_state.get() == &AppCursorState::Virtual,
+        };
+        if show_pointer {
             next_cursor_state.set(AppCursorState::Virtual);
             primary_window.cursor.grab_mode = CursorGrabMode::Confined;
-        } else if action_state.just_released(&PlayerMovement::UIToggle) {
+        } else if hide_pointer {
             next_cursor_state.set(AppCursorState::Locked);
             primary_window.cursor.grab_mode = CursorGrabMode::Locked;
             pointer.start_click_pos = None;
         }
         if (cursor_state.get().ne(&AppCursorState::Free)) {
-            primary_window.set_cursor_position(pointer.current_pos);
+            // Pages can't move the browser's cursor, so on the web only the drawn pointer moves
+            if cfg!(not(target_family = "wasm")) {
+                primary_window.set_cursor_position(pointer.current_pos);
+            }
             if (cursor_state.get().ne(&AppCursorState::Virtual)) {
                 pointer.current_pos = Some(Vec2 { x: primary_window.width() / 2.0, y: primary_window.height() / 2.0 });
-                if action_state.pressed(&PlayerMovement::Look) {
-                    let axis_pair = action_state.axis_pair(&PlayerMovement::Look).unwrap();
-                    transform.rotate_local_y( axis_pair.x() * time.delta_seconds() / settings.look_sensitivity * -1.0);
-                }
             } else {
+                let mut pointer_pos = pointer.current_pos.unwrap();
                 if action_state.pressed(&PlayerMovement::Look) {
                     if let Some(axis_pair) = action_state.axis_pair(&PlayerMovement::Look) {
-                        pointer.current_pos = Some(pointer.current_pos.unwrap().add(axis_pair.xy().div(settings.mouse_sensitivity)).clamp(Vec2::ZERO, Vec2 { x: primary_window.width(), y: primary_window.height() }));
+                        pointer_pos = pointer_pos.add(axis_pair.xy().div(settings.mouse_sensitivity));
                     }
                 }
+                if action_state.pressed(&PlayerMovement::StickLook) {
+                    let stick = action_state.axis_pair(&PlayerMovement::StickLook).unwrap().xy();
+                    pointer.stick_speed = (pointer.stick_speed.max(POINTER_STICK_MIN_SPEED) + POINTER_STICK_ACCELERATION * time.delta_seconds()).min(POINTER_STICK_MAX_SPEED);
+                    // Screen y grows downwards, stick y grows upwards
+                    pointer_pos += stick * Vec2::new(1.0, -1.0) * pointer.stick_speed * settings.gamepad.pointer_speed * time.delta_seconds();
+                } else {
+                    pointer.stick_speed = 0.0;
+                }
+                pointer.current_pos = Some(pointer_pos.clamp(Vec2::ZERO, Vec2 { x: primary_window.width(), y: primary_window.height() }));
             }
-            if action_state.pressed(&PlayerMovement::Move) {
-                let axis_pair = action_state.clamped_axis_pair(&PlayerMovement::Move).unwrap();
-                let velocity =((transform.left() * axis_pair.x() * -1.0 )+( transform.forward() * axis_pair.y()))
-                    *movement_acceleration.0 * time.delta().as_secs_f32() * crate::SPEED;
-                linear_velocity.x = velocity.x ;
-                linear_velocity.z = velocity.z ;
+        }
+    }
+}
+
+type SteerQuery<'w, 's> = Query<'w, 's, (&'static ActionState<PlayerMovement>, &'static mut MovementIntent, &'static JumpImpulse,
+                                          &'static mut LinearVelocity, &'static mut Transform, Has<Grounded>), Without<CameraPitch>>;
 
+/// Turns the player's input into where their body is headed: turning with the look actions, the
+/// [`MovementIntent`] for the next fixed ticks, and jumps. Needs no window, so replays run it headless,
+/// where there's no cursor to free and the settings are the defaults.
+fn steer(time: Res<Time>,
+         settings: Option<Res<Persistent<Settings>>>,
+         cursor_state: Option<Res<State<AppCursorState>>>,
+         mut controllers: SteerQuery,
+         mut q_camera: Query<(&mut CameraPitch, &PlayerCamera)>) {
+    let cursor_state = cursor_state.map_or(AppCursorState::Locked, |cursor_state| cursor_state.get().clone());
+    if cursor_state == AppCursorState::Free {
+        return;
+    }
+    let default_settings;
+    let settings = match &settings {
+        Some(settings) => settings.get(),
+        None => {
+            default_settings = Settings::default();
+            &default_settings
+        }
+    };
+    let camera_mode = q_camera.get_single().map_or(CameraMode::FirstPerson, |(_, camera)| camera.mode);
+    for (action_state, mut intent, jump_impulse, mut linear_velocity, mut transform, is_grounded) in &mut controllers {
+        // With the pointer showing, looking moves the pointer rather than the player
+        if cursor_state != AppCursorState::Virtual {
+            // Mouse motion is how far the mouse moved this frame, the stick is how fast to turn
+            let mut look = Vec2::ZERO;
+            if action_state.pressed(&PlayerMovement::Look) {
+                look += action_state.axis_pair(&PlayerMovement::Look).unwrap().xy() * time.delta_seconds() / settings.look_sensitivity;
+            }
+            if action_state.pressed(&PlayerMovement::StickLook) {
+                look += action_state.axis_pair(&PlayerMovement::StickLook).unwrap().xy() * Vec2::new(1.0, -1.0) * time.delta_seconds() * settings.gamepad.look_speed;
+            }
+            // The floor plan view doesn't turn with the player
+            if look != Vec2::ZERO && camera_mode != CameraMode::TopDown {
+                transform.rotate_local_y(-look.x);
+                if let Ok((mut pitch, _)) = q_camera.get_single_mut() {
+                    let invert = if settings.invert_y { 1.0 } else { -1.0 };
+                    pitch.angle = (pitch.angle + look.y * invert).clamp(-pitch.limit, pitch.limit);
+                }
+            }
+        }
+        intent.wish = Vector::ZERO;
+        if action_state.pressed(&PlayerMovement::Move) {
+            let axis_pair = action_state.clamped_axis_pair(&PlayerMovement::Move).unwrap();
+            intent.wish = if camera_mode == CameraMode::TopDown {
+                // Up on the stick is up the screen, which is north in the floor plan view
+                Vector::new(axis_pair.x(), 0.0, -axis_pair.y()).clamp_length_max(1.0)
+            } else {
+                ((transform.left() * axis_pair.x() * -1.0 )+( transform.forward() * axis_pair.y())).clamp_length_max(1.0)
+            };
+        }
+        intent.sprint = action_state.pressed(&PlayerMovement::Sprint);
+        if is_grounded && action_state.just_pressed(&PlayerMovement::Jump) {
+            linear_velocity.y = jump_impulse.0;
+        }
+    }
+}
+
+/// Offset from a collider's position down to its lowest point.
+fn collider_foot(collider: &Collider) -> Scalar {
+    collider.shape_scaled().compute_local_aabb().mins.y
+}
+
+type CrouchQuery<'w, 's> = Query<'w, 's, (Entity, &'static ActionState<PlayerMovement>, &'static mut Crouch, &'static Collider,
+                                          &'static mut ShapeCaster, &'static mut Transform, &'static Rotation)>;
+
+/// Swaps to the crouching collider while crouch is held, standing back up once there is room overhead.
+/// The new collider goes in through commands, as the spatial query is still reading the old one.
+fn crouch(mut commands: Commands, spatial_query: SpatialQuery, mut q_controllers: CrouchQuery) {
+    for (entity, action_state, mut crouch, collider, mut ground_caster, mut transform, rotation) in &mut q_controllers {
+        let crouching = action_state.pressed(&PlayerMovement::Crouch);
+        if crouching == crouch.crouched {
+            continue;
+        }
+        let target = if crouching { crouch.crouching.clone() } else { crouch.standing.clone() };
+        // Keep the feet where they are, so the camera drops and rises with the capsule
+        let translation = transform.translation + Vec3::Y * (collider_foot(collider) - collider_foot(&target));
+        if !crouching && !spatial_query.shape_intersections(&target,
+                                                            translation,
+                                                            rotation.0,
+                                                            SpatialQueryFilter::from_mask([GameLayer::Environment, GameLayer::RaycastInteractible])
+                                                                .with_excluded_entities([entity])).is_empty() {
+            continue;
+        }
+        let mut caster_shape = target.clone();
+        caster_shape.set_scale(Vector::ONE * 0.99, 10);
+        ground_caster.shape = caster_shape;
+        transform.translation = translation;
+        commands.entity(entity).insert(target);
+        crouch.crouched = crouching;
+    }
+}
+
+/// Updates the [`Grounded`] status for character controllers.
+fn update_grounded(
+    mut commands: Commands,
+    mut query: Query<
+        (Entity, &ShapeHits, &Rotation, Option<&MaxSlopeAngle>),
+        With<CharacterController>,
+    >,
+) {
+    for (entity, hits, rotation, max_slope_angle) in &mut query {
+        // The character is grounded if the shape caster has a hit with a normal
+        // that isn't too steep.
+        let is_grounded = hits.iter().any(|hit| {
+            if let Some(angle) = max_slope_angle {
+                rotation.rotate(-hit.normal2).angle_between(Vector::Y).abs() <= angle.0
+            } else {
+                true
+            }
+        });
+
+        if is_grounded {
+            commands.entity(entity).insert(Grounded);
+        } else {
+            commands.entity(entity).remove::<Grounded>();
+        }
+    }
+}
+
+/// Where the player starts out in the current building, and is put back when they fall out of the world.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct PlayerSpawn(pub Vec3);
+
+/// Puts the player at the [`PlayerSpawn`] when it moves, which it does whenever a building is entered,
+/// and when they've fallen too far below it to ever land on anything.
+fn respawn_player(spawn: Option<Res<PlayerSpawn>>,
+                  mut q_player: Query<(&mut Transform, &mut LinearVelocity), With<CharacterController>>) {
+    let Some(spawn) = spawn else {
+        return;
+    };
+    for (mut transform, mut linear_velocity) in &mut q_player {
+        if spawn.is_changed() || transform.translation.y < spawn.0.y - FALL_LIMIT {
+            transform.translation = spawn.0;
+            linear_velocity.0 = Vector::ZERO;
+        }
+    }
+}
+
+/// Applies [`ControllerGravity`] to character controllers.
+fn apply_gravity(
+    time: Res<Time>,
+    mut controllers: Query<(&ControllerGravity, &mut LinearVelocity)>,
+) {
+    for (gravity, mut linear_velocity) in &mut controllers {
+        linear_velocity.0 += gravity.0 * time.delta_seconds();
+    }
+}
+
+type MovementQuery<'w, 's> = Query<'w, 's, (&'static MovementIntent, &'static MovementSettings, &'static mut LinearVelocity,
+                                             Option<&'static mut Stamina>, Option<&'static Crouch>)>;
+
+/// Accelerates character controllers towards their [`MovementIntent`] in the XZ plane,
+/// at a speed set by sprinting, crouching and how the player is feeling.
+fn apply_movement(time: Res<Time>,
+                  day_timer: Res<DayTimer>,
+                  mut controllers: MovementQuery) {
+    let delta = time.delta_seconds();
+    for (intent, settings, mut linear_velocity, stamina, crouch) in &mut controllers {
+        let crouched = crouch.is_some_and(|crouch| crouch.crouched);
+        let moving = intent.wish != Vector::ZERO;
+        let mut speed_factor = day_timer.speed_factor();
+        if let Some(mut stamina) = stamina {
+            if moving && !crouched && intent.sprint && stamina.current > 0.0 {
+                stamina.current = (stamina.current - STAMINA_DRAIN * delta).max(0.0);
+                speed_factor *= SPRINT_FACTOR;
+            } else {
+                stamina.current = (stamina.current + STAMINA_RECOVERY * delta).min(stamina.max);
             }
         }
+        if crouched {
+            speed_factor *= CROUCH_FACTOR;
+        }
+        linear_velocity.0 = accelerate(linear_velocity.0, intent.wish, settings, speed_factor, delta);
     }
 }
 
-// / Applies [`ControllerGravity`] to character controllers.
+type SlideQuery<'w, 's> = Query<'w, 's, (Entity, &'static RigidBody, &'static Collider, &'static mut Position, &'static Rotation,
+                                            &'static mut LinearVelocity, &'static mut SlideVelocity, Option<&'static MaxSlopeAngle>,
+                                            Option<&'static MaxStepHeight>, Has<Grounded>), With<CharacterController>>;
+
+/// Moves character controllers with collide and slide at the start of each physics step, rather than
+/// leaving it to the solver, so they slide along walls instead of jittering on or tunnelling through them.
+/// Their velocity is parked in [`SlideVelocity`] until the step is over, so the body isn't moved twice.
+/// The spatial query reads every position, so each controller is read, swept and written back in turn.
+fn slide_controllers(time: Res<Time>, mut queries: ParamSet<(SpatialQuery, SlideQuery)>) {
+    let controllers: Vec<_> = queries.p1().iter()
+        .filter(|(_, rb, ..)| rb.is_kinematic())
+        .map(|(entity, _, collider, position, rotation, linear_velocity, _, max_slope_angle, step_height, is_grounded)| {
+            let step = step_height.zip(max_slope_angle).filter(|_| is_grounded).map(|(step_height, max_slope_angle)| (step_height.0, max_slope_angle.0));
+            (entity, collider.clone(), position.0, *rotation, linear_velocity.0, step)
+        })
+        .collect();
+    for (entity, collider, position, rotation, velocity, step) in controllers {
+        let result = {
+            let spatial_query = queries.p0();
+            let filter = SpatialQueryFilter::from_mask([GameLayer::Environment, GameLayer::RaycastInteractible])
+                .with_excluded_entities([entity]);
+            let mut cast = |origin: Vector, direction: Direction3d, max_distance: Scalar| {
+                spatial_query.cast_shape(&collider, origin, rotation.0, direction, max_distance, true, filter.clone())
+                    .map(|hit| SlideHit { distance: hit.time_of_impact, normal: -rotation.rotate(hit.normal2) })
+            };
+            let motion = velocity * time.delta_seconds();
+            let result = collide_and_slide(position, motion, &mut cast);
+            match step {
+                Some((step_height, max_slope_angle)) => step_up(position, motion, &result, step_height, max_slope_angle, &mut cast).unwrap_or(result),
+                None => result,
+            }
+        };
+        let mut controllers = queries.p1();
+        let Ok((_, _, _, mut position, _, mut linear_velocity, mut slide_velocity, ..)) = controllers.get_mut(entity) else {
+            continue;
+        };
+        position.0 = result.position;
+        slide_velocity.0 = result.clip_velocity(linear_velocity.0);
+        linear_velocity.0 = Vector::ZERO;
+    }
+}
 
-/// Slows down movement in the XZ plane.
-fn apply_movement_damping(mut query: Query<(&MovementDampingFactor, &mut LinearVelocity)>) {
-    for (damping_factor, mut linear_velocity) in &mut query {
-        // We could use `LinearDamping`, but we don't want to dampen movement along the Y axis
-        linear_velocity.x *= damping_factor.0;
-        linear_velocity.z *= damping_factor.0;
+/// Hands character controllers their velocity back once the physics step is done.
+fn restore_slide_velocity(mut controllers: Query<(&SlideVelocity, &mut LinearVelocity), With<CharacterController>>) {
+    for (slide_velocity, mut linear_velocity) in &mut controllers {
+        linear_velocity.0 = slide_velocity.0;
     }
 }
 
 /// Kinematic bodies do not get pushed by collisions by default,
-/// so it needs to be done manually.
+/// so it needs to be done manually. [`slide_controllers`] keeps characters
+/// out of walls, this cleans up whatever still overlaps them, like doors
+/// swinging shut on the player.
 ///
 /// This system performs very basic collision response for kinematic
 /// character controllers by pushing them along their contact normals
@@ -304,7 +812,6 @@ fn kinematic_controller_collisions(
             &RigidBody,
             &mut Position,
             &Rotation,
-            &mut LinearVelocity,
         ),
         With<CharacterController>,
     >,
@@ -326,7 +833,7 @@ fn kinematic_controller_collisions(
         // Get the body of the character controller and whether it is the first
         // or second entity in the collision.
         let is_first: bool;
-        let (rb, mut position, rotation, mut linear_velocity) =
+        let (rb, mut position, rotation) =
             if let Ok(character) = character_controllers.get_mut(collider_parent1.get()) {
                 is_first = true;
                 character
@@ -359,30 +866,49 @@ fn kinematic_controller_collisions(
         }
     }
 }
-const RAY_RANGE: f32 = 5.0;
+const RAY_RANGE: f32 = INTERACTION_RANGE;
+const MAX_PITCH: f32 = 85.0 * PI / 180.0;
+const GRAVITY: Scalar = 9.81;
+/// Virtual pointer speeds under the right stick, in pixels per second.
+const POINTER_STICK_MIN_SPEED: f32 = 200.0;
+const POINTER_STICK_MAX_SPEED: f32 = 1400.0;
+const POINTER_STICK_ACCELERATION: f32 = 1600.0;
+const SPRINT_FACTOR: Scalar = 1.8;
+const CROUCH_FACTOR: Scalar = 0.5;
+/// Stamina used per second of sprinting.
+const STAMINA_DRAIN: Scalar = 25.0;
+const STAMINA_RECOVERY: Scalar = 15.0;
+const MAX_STEP_HEIGHT: Scalar = 0.25;
+/// How far below its spawn point the player can fall before being put back there.
+const FALL_LIMIT: f32 = 50.0;
+/// Physics steps at 60Hz, the same as the physics default.
+const PHYSICS_TICK: Duration = Duration::from_micros(16_667);
+/// Longest frame physics catches up on in full, the same as Bevy's own clocks. The physics default
+/// is one tick, which moves the player in slow motion below 60 fps.
+const PHYSICS_CATCH_UP: Duration = Duration::from_millis(250);
+type PlayerBodyQuery<'w, 's> = Query<'w, 's, (&'static ActionState<PlayerMovement>, &'static InputMap<PlayerMovement>, &'static GlobalTransform),
+                                      With<CharacterController>>;
+
 fn mesh_snip_select_system(
     mut commands: Commands,
-    mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
-    mut q_player: Query<(&ActionState<PlayerMovement>, &GlobalTransform, &Camera), (With<CharacterController>,With<Camera>)>,
+    mut actions: EventWriter<InteractableAction>,
+    q_player: PlayerBodyQuery,
+    q_camera: Query<(&GlobalTransform, &Camera), With<CameraPitch>>,
     mut q_pointer: Query<&mut VirtualPointer, With<VirtualPointer>>,
-    mut q_door_egui_interactable_empty: Query<&DoorEguiInteractableEmpty,(With<DoorEguiInteractableEmpty>,Without<DoorEguiInteractableComponent>)>,
-    mut q_door_egui_interactable_component: Query<(&DoorEguiInteractableComponent,&GlobalTransform,Entity,&mut DoorEguiInteractableEmpty),With<DoorEguiInteractableComponent>>,
+    q_interactables: Query<&dyn Interactable>,
+    mut selection: ResMut<Selection>,
+    q_hovered: Query<(&ColliderAabb, Option<&InteractPrompt>)>,
     mut egui_contexts: EguiContexts,
     mut gizmos: Gizmos,
     cursor_state: Res<State<AppCursorState>>,
+    settings: Res<Persistent<Settings>>,
     spatial_query: SpatialQuery) {
     let ctx = egui_contexts.ctx_mut();
-    let (mut action_state, transform, camera) = q_player.single_mut(); // Cast ray and print first hit
+    let (action_state, input_map, body) = q_player.single();
+    let (transform, camera) = q_camera.single(); // Cast ray and print first hit
+    // Reach is measured from the body, so pulling the camera back doesn't shorten it
+    let reach = RAY_RANGE + transform.translation().distance(body.translation());
     let mut pointer = q_pointer.get_single_mut().unwrap();
-    for mut door in  &mut q_door_egui_interactable_component{
-        if transform.translation().distance(door.1.translation()) > RAY_RANGE{
-            commands.get_entity(door.2).unwrap().remove::<DoorEguiInteractableComponent>();
-        }
-        if(!door.3.window_open){
-            commands.get_entity(door.2).unwrap().remove::<DoorEguiInteractableComponent>();
-            door.3.window_open = true;
-        }
-    }
 
     if cursor_state.eq(&AppCursorState::Virtual) {
         if !ctx.is_using_pointer() && !ctx.is_pointer_over_area() {
@@ -402,21 +928,21 @@ fn mesh_snip_select_system(
                 let Some(left_top) = camera.viewport_to_world(&transform, Vec2 { x: start_pointer_pos.x.min(end_pointer_pos.x), y: start_pointer_pos.y.max(end_pointer_pos.y) }) else {
                     return;
                 };
-                gizmos.linestrip(vec![left_bottom.get_point(RAY_RANGE),
-                                      right_bottom.get_point(RAY_RANGE),
-                                      right_top.get_point(RAY_RANGE),
-                                      left_top.get_point(RAY_RANGE),
-                                      left_bottom.get_point(RAY_RANGE)], Color::ANTIQUE_WHITE);
+                gizmos.linestrip(vec![left_bottom.get_point(reach),
+                                      right_bottom.get_point(reach),
+                                      right_top.get_point(reach),
+                                      left_top.get_point(reach),
+                                      left_bottom.get_point(reach)], settings.accessibility.palette.hover());
                 if action_state.just_released(&PlayerMovement::Click) {
                     let intersections = spatial_query.shape_intersections(
                         &Collider::trimesh(vec![left_top.origin,
                                                 right_top.origin,
                                                 left_bottom.origin,
                                                 right_bottom.origin,
-                                                left_top.get_point(RAY_RANGE),
-                                                right_top.get_point(RAY_RANGE),
-                                                left_bottom.get_point(RAY_RANGE),
-                                                right_bottom.get_point(RAY_RANGE)],
+                                                left_top.get_point(reach),
+                                                right_top.get_point(reach),
+                                                left_bottom.get_point(reach),
+                                                right_bottom.get_point(reach)],
                                            vec![[0, 1, 2], // Side 0
                                                 [2, 1, 3],
                                                 [4, 0, 6], // Side 1
@@ -433,14 +959,11 @@ fn mesh_snip_select_system(
                         Quat::default(),
                         SpatialQueryFilter::from_mask(GameLayer::RaycastInteractible),
                     );
-                    let entity = intersections.get(0);
-                    if let Some(entity_unwrapped) = entity {
-                        let entity_unwrapped_clone = entity_unwrapped.clone();
-                        let mut result =  q_door_egui_interactable_empty.get_mut(entity_unwrapped_clone);
-                        if result.is_ok(){
-                            if(q_door_egui_interactable_component.get_mut(entity_unwrapped_clone).is_err()){
-                                commands.get_entity(entity_unwrapped_clone).unwrap().insert(result.unwrap().build_interactable_component());
-                            }
+                    selection.0 = intersections.into_iter().filter(|entity| q_interactables.contains(*entity)).collect();
+                    // A single pick opens straight away, a group goes through the selection window
+                    if let [entity] = selection.0.iter().copied().collect::<Vec<_>>()[..] {
+                        for interactable in q_interactables.get(entity).into_iter().flatten() {
+                            interactable.select(entity, &mut commands);
                         }
                     }
                     pointer.start_click_pos = None;
@@ -451,12 +974,25 @@ fn mesh_snip_select_system(
                 };
                 let intersections = spatial_query.cast_ray(cursor.origin,
                                                            cursor.direction,
-                                                           RAY_RANGE,
+                                                           reach,
                                                            true,
                                                            SpatialQueryFilter::from_mask(GameLayer::RaycastInteractible),
                 );
-                if intersections.is_some() {
-                    ctx.debug_painter().circle_filled(Pos2::from(pointer.current_pos.unwrap().as_ref()), 8.0, Color32::from_rgb(255, 255, 255));
+                let hovered = intersections.and_then(|hit| Some((hit.entity, q_interactables.get(hit.entity).ok()?)));
+                if let Some((entity, interactables)) = hovered {
+                    let pointer_pos = Pos2::from(pointer.current_pos.unwrap().as_ref());
+                    ctx.debug_painter().circle_filled(pointer_pos, 8.0, Color32::from_rgb(255, 255, 255));
+                    if let Ok((aabb, _)) = q_hovered.get(entity) {
+                        highlight(&mut gizmos, aabb, settings.accessibility.palette.hover());
+                    }
+                    egui::show_tooltip_at(ctx, Id::new("interactable hover"), Some(pointer_pos + egui::vec2(12.0, 12.0)), |ui| {
+                        for interactable in &interactables {
+                            ui.strong(interactable.name());
+                            for action in interactable.batch_actions() {
+                                ui.label(*action);
+                            }
+                        }
+                    });
                 } else {
                     ctx.debug_painter().circle_filled(Pos2::from(pointer.current_pos.unwrap().as_ref()), 4.0, Color32::from_rgb(100, 100, 100));
                 }
@@ -465,23 +1001,91 @@ fn mesh_snip_select_system(
             ctx.debug_painter().circle_filled(Pos2::from(pointer.current_pos.unwrap().as_ref()), 4.0, Color32::from_rgb(100, 100, 100));
 
         }
+    } else if cursor_state.eq(&AppCursorState::Locked) {
+        // Locked mode interacts with whatever is under the crosshair
+        let centre = pointer.current_pos.unwrap();
+        ctx.debug_painter().circle_filled(Pos2::from(centre.as_ref()), 4.0, Color32::from_rgb(100, 100, 100));
+        let Some(cursor) = camera.viewport_to_world(transform, centre) else {
+            return;
+        };
+        let Some(hit) = spatial_query.cast_ray(cursor.origin,
+                                               cursor.direction,
+                                               reach,
+                                               true,
+                                               SpatialQueryFilter::from_mask(GameLayer::RaycastInteractible)) else {
+            return;
+        };
+        let Ok(interactables) = q_interactables.get(hit.entity) else {
+            return;
+        };
+        let prompt = q_hovered.get(hit.entity).ok().and_then(|(aabb, prompt)| {
+            highlight(&mut gizmos, aabb, settings.accessibility.palette.hover());
+            prompt
+        });
+        if action_state.just_pressed(&PlayerMovement::Interact) {
+            for interactable in &interactables {
+                if interactable.acts_on_interact() {
+                    actions.send(InteractableAction { entity: hit.entity, action: INTERACT_ACTION });
+                } else {
+                    interactable.select(hit.entity, &mut commands);
+                }
+            }
+        }
+        let key = input_map.get(&PlayerMovement::Interact)
+            .and_then(|inputs| inputs.first())
+            .map(input_label)
+            .unwrap_or_else(|| "Unbound".to_string());
+        let prompts: Vec<String> = match prompt {
+            Some(prompt) => vec![prompt.0.clone()],
+            None => interactables.iter().map(|interactable| interactable.prompt()).collect(),
+        };
+        for prompt in prompts {
+            ctx.debug_painter().text(Pos2::new(centre.x, centre.y + 24.0),
+                                     egui::Align2::CENTER_TOP,
+                                     format!("{key} – {prompt}"),
+                                     egui::FontId::proportional(18.0),
+                                     Color32::WHITE);
+        }
     }
 }
-fn paused_update(mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
-                 mut next_state: ResMut<NextState<MyAppState>>,
-                 mut q_player: Query<&ActionState<PlayerMovement>, (With<CharacterController>,With<Camera>)>,
+/// Frees the cursor for the pause menu, however the game was paused.
+fn free_cursor(mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
+               mut next_cursor_state: ResMut<NextState<AppCursorState>>,
+               mut q_pointer: Query<&mut VirtualPointer>) {
+    if let Ok(mut primary_window) = q_windows.get_single_mut() {
+        primary_window.cursor.grab_mode = CursorGrabMode::None;
+    }
+    next_cursor_state.set(AppCursorState::Free);
+    for mut pointer in &mut q_pointer {
+        pointer.start_click_pos = None;
+    }
+}
+
+fn lock_cursor(mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
+               mut next_cursor_state: ResMut<NextState<AppCursorState>>) {
+    if let Ok(mut primary_window) = q_windows.get_single_mut() {
+        primary_window.cursor.grab_mode = CursorGrabMode::Locked;
+        primary_window.cursor.visible = false;
+    }
+    next_cursor_state.set(AppCursorState::Locked);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn paused_update(q_windows: Query<&Window, With<PrimaryWindow>>,
+                 mut game_commands: EventWriter<GameCommand>,
+                 mut q_player: Query<&ActionState<PlayerMovement>, With<CharacterController>>,
                  mut contexts: EguiContexts,
-                 mut next_cursor_state: ResMut<NextState<AppCursorState>>,
                  mut settings: ResMut<Persistent<Settings>>,
+                 mut settings_status: ResMut<SettingsStatus>,
                  mut q_pointer: Query<&mut VirtualPointer, With<VirtualPointer>>,
+                 rebind_input: RebindInput,
+                 mut rebinding: Local<Rebinding>,
                  mut is_settings_open: Local<bool>) {
     let mut action_state = q_player.single_mut();
-    let mut primary_window = q_windows.single_mut();
-    if action_state.just_pressed(&PlayerMovement::Pause) {
-        primary_window.cursor.grab_mode = CursorGrabMode::Locked;
-        primary_window.cursor.visible = false;
-        next_cursor_state.set(AppCursorState::Locked);
-        next_state.set(MyAppState::InGame);
+    let primary_window = q_windows.single();
+    // Escape may be what's being bound, rather than a request to unpause
+    if action_state.just_pressed(&PlayerMovement::Pause) && !rebinding.is_capturing() {
+        game_commands.send(GameCommand::Resume);
     }
     let mut pointer = q_pointer.get_single_mut().unwrap();
     let ctx = contexts.ctx_mut();
@@ -491,22 +1095,56 @@ fn paused_update(mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
 
     if *(is_settings_open) {
         egui::Window::new("Settings").show(ctx, |ui| {
+            settings_status_ui(ui, &mut settings_status);
             ui.horizontal(|ui| {
-                ui.label("Horizontal Look Sensitivity");
+                ui.label("Look Sensitivity");
                 if ui.add(egui::Slider::new(&mut settings.look_sensitivity, 1.0..=1000.0)).changed() {
-                    settings.persist().expect("failed to save new settings");
+                    save_settings(&settings, &mut settings_status);
                 }
             });
+            if ui.checkbox(&mut settings.invert_y, "Invert Vertical Look").changed() {
+                save_settings(&settings, &mut settings_status);
+            }
             ui.horizontal(|ui| {
                 ui.label("Cursor Horizontal Sensitivity");
                 if ui.add(egui::Slider::new(&mut settings.mouse_sensitivity.x, 0.05..=2.0)).changed() {
-                    settings.persist().expect("failed to save new settings");
+                    save_settings(&settings, &mut settings_status);
                 }
             });
             ui.horizontal(|ui| {
                 ui.label("Cursor Vertical Sensitivity");
                 if ui.add(egui::Slider::new(&mut settings.mouse_sensitivity.y, 0.05..=2.0)).changed() {
-                    settings.persist().expect("failed to save new settings");
+                    save_settings(&settings, &mut settings_status);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Gamepad Deadzone");
+                if ui.add(egui::Slider::new(&mut settings.gamepad.deadzone, 0.0..=0.5)).changed() {
+                    let deadzone = settings.gamepad.deadzone;
+                    apply_deadzone(&mut settings.controls, deadzone);
+                    save_settings(&settings, &mut settings_status);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Gamepad Look Speed");
+                if ui.add(egui::Slider::new(&mut settings.gamepad.look_speed, 0.5..=8.0)).changed() {
+                    save_settings(&settings, &mut settings_status);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Gamepad Cursor Speed");
+                if ui.add(egui::Slider::new(&mut settings.gamepad.pointer_speed, 0.25..=3.0)).changed() {
+                    save_settings(&settings, &mut settings_status);
+                }
+            });
+            ui.collapsing("Accessibility", |ui| {
+                if accessibility_ui(ui, &mut settings.accessibility) {
+                    save_settings(&settings, &mut settings_status);
+                }
+            });
+            ui.collapsing("Controls", |ui| {
+                if controls_ui(ui, &mut settings.controls, &mut rebinding, &rebind_input) {
+                    save_settings(&settings, &mut settings_status);
                 }
             });
         });
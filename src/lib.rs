@@ -3,16 +3,35 @@ extern crate static_assertions;
 
 pub mod states;
 pub mod work;
-mod building;
+pub mod building;
+pub mod collide_and_slide;
+mod accessibility;
+mod controls;
+pub mod castle;
+pub mod floor_plan;
+pub mod gltf_export;
+pub mod locomotion;
+pub mod replay;
+pub mod simulation;
+pub mod door;
+pub mod interactable;
+pub mod lock;
 mod kinematic_character_3d;
+mod player_camera;
+mod settings;
+mod storage;
+#[cfg(target_family = "wasm")]
+mod web;
 use kinematic_character_3d::*;
+pub use kinematic_character_3d::{CharacterControllerBundle, CharacterMovementPlugin, GameLayer, PlayerMovement};
 use serde::{Serialize, Deserialize};
 use bevy::pbr::CascadeShadowConfigBuilder;
 use rand::prelude::*;
 use strum::IntoEnumIterator;
 use std::f32::consts::PI;
 use std::ops::{Add, Div, Mul};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use bevy::prelude::*;
 use bevy_egui::{egui, EguiContext, EguiContexts, EguiPlugin, EguiSettings, EguiUserTextures};
 use wasm_bindgen::prelude::*;
@@ -20,17 +39,19 @@ use states::*;
 
 use work::*;
 use bevy_xpbd_3d::prelude::*;
-use bevy::time::Stopwatch;
+use bevy::input::InputPlugin;
+use bevy::scene::ScenePlugin;
+use bevy::time::{Stopwatch, TimeUpdateStrategy};
 use bevy::utils::{HashMap, HashSet};
 use bevy_egui::egui::{Color32, Context, Id, Pos2, Rangef, TextureId};
 use iyes_progress::prelude::*;
 use leafwing_input_manager::prelude::*;
-use bevy::window::{CursorGrabMode, PrimaryWindow};
+use bevy::window::{PrimaryWindow, WindowMode, WindowResolution};
 use bevy_ecs::component::{SparseStorage, TableStorage};
-use leafwing_input_manager::prelude::InputKind::Mouse;
 use leafwing_input_manager::prelude::*;
 
 use bevy_persistent::prelude::*;
+use bevy_persistent::storage::Storage;
 use bevy_ecs::prelude::*;
 use bevy_ecs::schedule::{ScheduleLabel, SystemConfigs};
 use bevy_ecs::system::{FunctionSystem, SystemParam, SystemParamItem};
@@ -39,18 +60,41 @@ use bevy_reflect::prelude::*;
 
 const TIME_FACTOR: u32 = 8;
 const TOP_UI_HEIGHT_FRACTION: f32 = 7.5;
-const SPEED: f32 = 10.0;
+/// Walking speed of the player, in units per second.
+const SPEED: f32 = 5.0;
 pub const DOOR_WIDTH: f32 = 1.0;
+/// How high the middle of the standing player's capsule sits above the floor.
+const PLAYER_HALF_HEIGHT: f32 = 0.9;
+/// How far the ground reaches out past the building on every side.
+const GROUND_MARGIN: f32 = 100.0;
+const GROUND_THICKNESS: f32 = 0.1;
+/// How long each frame of a [`headless_app`] takes, unless a replay says otherwise.
+const HEADLESS_FRAME: Duration = Duration::from_millis(16);
+const HEADLESS_LOCKS_FILE: &str = "dracula_headless_door_locks.ron";
 
 static ROTATE_SPEED: f32 = -100.0;
 
 static SOLAR_ICON_SIZE: egui::Vec2 = egui::Vec2::new(64.0, 64.0);
 
 static LUNAR_ICON_SIZE: egui::Vec2 = egui::Vec2::new(32.0, 32.0);
+/// The hour the day clock starts at, with the sun coming up.
+const SUNRISE_HOUR: f32 = 6.0;
+/// Where the sun or moon stays with reduced motion on, part way up the sky.
+const PINNED_SKY_ANGLE: f32 = 3.0 * PI / 4.0;
 
 #[derive(Component)]
 struct BuildingMarker;
 
+/// Which generator strategy lays out the building entered when going indoors.
+#[derive(Resource, Default, Debug, Clone, PartialEq, Eq)]
+pub enum BuildingLayout {
+    #[default]
+    House,
+    Castle,
+    /// A hand-authored plan from `assets/floor_plans`, by file stem.
+    FloorPlan(String),
+}
+
 
 
 
@@ -62,6 +106,34 @@ pub struct DoorMarker {
     door_enum: DoorEnum,
 }
 
+/// Where the game's randomness comes from. Seeded, so a recorded session lays out the same buildings when replayed.
+#[derive(Resource)]
+pub struct GameRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, rng: StdRng::seed_from_u64(seed) }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::new(random())
+    }
+}
+
+#[derive(Resource)]
 struct DayTimer {
     stopwatch: Stopwatch,
     timefactor: u32,
@@ -86,6 +158,25 @@ impl Default for DayTimer {
     }
 }
 
+impl DayTimer {
+    /// Moves the clock to `hour` on the current day, where a day is 2π seconds of the stopwatch starting at sunrise.
+    fn set_hour(&mut self, hour: f32) {
+        let day = (self.stopwatch.elapsed_secs() / (2.0 * PI)).floor();
+        let time_of_day = (hour - SUNRISE_HOUR).rem_euclid(24.0) / 24.0;
+        self.stopwatch.set_elapsed(Duration::from_secs_f32((day + time_of_day) * 2.0 * PI));
+    }
+
+    /// How long the player has been awake, as a fraction of a day.
+    fn tiredness(&self) -> f32 {
+        (self.sleepwatch.elapsed_secs() / (2.0 * PI)).clamp(0.0, 1.0)
+    }
+
+    /// Tired players slow down and happy ones pick up the pace.
+    fn speed_factor(&self) -> f32 {
+        (1.0 - 0.5 * self.tiredness()) * (1.0 + 0.1 * self.happiness.clamp(-5, 5) as f32)
+    }
+}
+
 #[derive(Default)]
 struct BevyEguiImageWrapper {
     id: Option<TextureId>,
@@ -131,150 +222,333 @@ struct EmojiSprites {
 
 #[derive(Resource, Serialize, Deserialize)]
 struct Settings {
+    /// Which [`settings::migrate`] steps the file has been through. Missing from files older than versioning.
+    #[serde(default)]
+    version: u32,
     mouse_sensitivity: Vec2,
     look_sensitivity: f32,
+    #[serde(default)]
+    invert_y: bool,
+    #[serde(default = "default_input_map")]
+    controls: InputMap<PlayerMovement>,
+    #[serde(default)]
+    gamepad: GamepadSettings,
+    #[serde(default)]
+    accessibility: AccessibilitySettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            mouse_sensitivity: Vec2 { x: 1.0, y: 1.0 },
+            look_sensitivity: 50.0,
+            invert_y: false,
+            controls: default_input_map(),
+            gamepad: GamepadSettings::default(),
+            accessibility: AccessibilitySettings::default(),
+        }
+    }
+}
+
+/// The canvas in `index.html` the game draws to on the web.
+const CANVAS_SELECTOR: &str = "#dracula";
+
+/// How to start the game, for skipping past the menus while working on it.
+#[derive(Debug, Default, Clone)]
+pub struct LaunchOptions {
+    /// Lays the world out the same every time, instead of from a random seed.
+    pub seed: Option<u64>,
+    /// Goes straight through loading into this state, without the main menu.
+    pub skip_to: Option<MyGameState>,
+    /// Logical size of the window, in pixels.
+    pub window_size: Option<Vec2>,
+    pub fullscreen: bool,
+    /// Reads and writes the settings here instead of the user's config directory.
+    pub settings_path: Option<PathBuf>,
+    /// Hour of the day the clock starts at, from 0 to 24.
+    pub start_hour: Option<f32>,
 }
 
 #[wasm_bindgen(start)]
 pub fn start() {
-    App::new()
-        .add_plugins((DefaultPlugins, EguiPlugin, PhysicsPlugins::default(), CharacterControllerPlugin, ))
-        .add_plugins(
-            ProgressPlugin::new(MyAppState::LoadingScreen)
-                .continue_to(MyAppState::InGame)
-                .track_assets(), )
-        .add_systems(Update, (main_menu_gui_system.run_if(in_state(MyAppState::MainMenu)),
-                              (game_update_top_ui,
-                               game_update_work.run_if(in_state(MyGameState::Outdoors)).after(game_update_top_ui),
-                              ).run_if(in_state(MyAppState::InGame)),
-                              loading_game_update.after(TrackedProgressSet)
-                                  .run_if(in_state(MyAppState::LoadingScreen))))
-        .insert_state(MyGameState::Indoors)
-        .insert_state(AppCursorState::Free)
-        .add_systems(OnEnter(MyGameState::Indoors), load_room)
-        .add_systems(OnEnter(MyAppState::LoadingScreen), (loading_game_assets_enter))
-        .add_systems(OnExit(MyAppState::LoadingScreen), loading_game_assets_exit)
-        .insert_state(MyAppState::MainMenu)
-        .run();
-}
-
-fn setup_camera(mut commands: Commands) {
-    let mut input_map = InputMap::default();
-    input_map.insert(PlayerMovement::Move, VirtualDPad::wasd());
-    input_map.insert(PlayerMovement::UIToggle, KeyCode::ShiftLeft);
-    input_map.insert(PlayerMovement::Pause, KeyCode::Escape);
-    input_map.insert(PlayerMovement::Click, Mouse(MouseButton::Left));
-    input_map.insert(PlayerMovement::Look, DualAxis::mouse_motion());
-    commands.spawn((
-        Camera3dBundle {
-            transform: Transform::from_xyz(0.0, 0.9, 0.0)
-                .looking_at(Vec3 { x: 1.0, y: 0.9, z: 0.0 }, Vec3::Y),
-            ..default()
-        },
-        CharacterControllerBundle::new(Collider::capsule(1.0, 0.4), InputManagerBundle::with_map(input_map))
-            .with_movement(30.0, 0.92),
+    start_with(LaunchOptions::default());
+}
+
+pub fn start_with(options: LaunchOptions) {
+    let mut app = App::new();
+    let mut primary_window = Window {
+        canvas: Some(CANVAS_SELECTOR.into()),
+        ..default()
+    };
+    if let Some(size) = options.window_size {
+        primary_window.resolution = WindowResolution::new(size.x, size.y);
+    }
+    if options.fullscreen {
+        primary_window.mode = WindowMode::BorderlessFullscreen;
+    }
+    let mut presentation = PresentationPlugin::default();
+    #[cfg(not(target_family = "wasm"))]
+    if let Some(path) = options.settings_path {
+        presentation.settings_storage = Some(Storage::Filesystem { path });
+    }
+    app.add_plugins((
+        DefaultPlugins.set(WindowPlugin { primary_window: Some(primary_window), ..default() }),
+        SimulationPlugin,
+        WorldPlugin::default(),
+        presentation,
     ));
+    if let Some(seed) = options.seed {
+        app.insert_resource(GameRng::new(seed));
+    }
+    if let Some(hour) = options.start_hour {
+        app.world.resource_mut::<DayTimer>().set_hour(hour);
+    }
+    // Applied along with the initial states on the first frame, so the main menu is never shown
+    if let Some(game_state) = options.skip_to {
+        app.world.resource_mut::<NextState<MyAppState>>().set(MyAppState::LoadingScreen);
+        app.world.resource_mut::<NextState<MyGameState>>().set(game_state);
+    }
+    if let Ok(path) = std::env::var("DRACULA_RECORD") {
+        app.add_plugins(RecordPlugin::<PlayerMovement>::new(path));
+    }
+    if let Ok(path) = std::env::var("DRACULA_REPLAY") {
+        match InputRecording::<PlayerMovement>::load(&path) {
+            Ok(recording) => {
+                app.add_plugins(ReplayPlugin::new(recording));
+            }
+            Err(error) => error!("not replaying {path}: {error}"),
+        }
+    }
+    app.run();
+}
+
+/// The game without a window or renderer: the simulation, the building and the player's body, going
+/// straight past the main menu. It runs the same movement systems as the windowed game, so a recording
+/// replayed on it moves the player just as it did when it was played.
+pub fn headless_app(seed: u64) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        TransformPlugin,
+        HierarchyPlugin,
+        InputPlugin,
+        AssetPlugin::default(),
+        ScenePlugin,
+        SimulationPlugin,
+        // Kept apart from the player's own saves
+        WorldPlugin { lock_storage: Some(temporary_storage(HEADLESS_LOCKS_FILE)) },
+        CharacterMovementPlugin,
+    ))
+        .init_asset::<Mesh>()
+        .init_asset::<StandardMaterial>()
+        .insert_resource(GameRng::new(seed))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(HEADLESS_FRAME))
+        .add_systems(OnEnter(MyAppState::LoadingScreen), spawn_player_body);
+    app.world.resource_mut::<NextState<MyAppState>>().set(MyAppState::LoadingScreen);
+    app
+}
+
+/// Plays `recording` back on a [`headless_app`] as fast as it will go, returning where it left the player.
+pub fn replay_headless(recording: InputRecording<PlayerMovement>) -> Transform {
+    let mut app = headless_app(recording.seed);
+    app.add_plugins(ReplayPlugin::new(recording));
+    while !app.world.resource::<Replay<PlayerMovement>>().finished() {
+        app.update();
+    }
+    *app.world.query_filtered::<&Transform, With<CharacterController>>().single(&app.world)
+}
+
+/// The building the player is in and the physics that keeps them inside it.
+#[derive(Default)]
+pub struct WorldPlugin {
+    /// Where door locks are kept, when not in the usual place.
+    pub lock_storage: Option<Storage>,
+}
+
+impl Plugin for WorldPlugin {
+    fn build(&self, app: &mut App) {
+        let locks = match &self.lock_storage {
+            Some(storage) => LockPlugin { storage: storage.clone() },
+            None => LockPlugin::default(),
+        };
+        app
+            .add_plugins((PhysicsPlugins::default(), DoorPlugin, locks))
+            .init_resource::<BuildingLayout>()
+            .add_systems(OnEnter(MyGameState::Indoors), load_room);
+    }
+}
+
+/// Everything the player sees and handles: the menus and HUD, the player's body and camera, and their input.
+#[derive(Default)]
+pub struct PresentationPlugin {
+    /// Where the settings are kept, when not in the usual place.
+    pub settings_storage: Option<Storage>,
+}
+
+impl Plugin for PresentationPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = match &self.settings_storage {
+            Some(storage) => SettingsPlugin { storage: storage.clone() },
+            None => SettingsPlugin::default(),
+        };
+        app
+            .add_plugins((EguiPlugin, CharacterControllerPlugin, PlayerCameraPlugin, settings, ControlsPlugin, AccessibilityPlugin, InteractablePlugin))
+            .add_systems(Update, (main_menu_gui_system.run_if(in_state(MyAppState::MainMenu)),
+                                  top_ui_sprites_progress.track_progress()
+                                      .run_if(in_state(MyAppState::LoadingScreen)),
+                                  (game_update_top_ui.after(SimulationSet),
+                                   // Only draws the work panel, there's nothing at work to simulate yet
+                                   game_update_work.run_if(in_state(MyGameState::Outdoors)).after(game_update_top_ui),
+                                  ).run_if(in_state(MyAppState::InGame)),
+                                  loading_game_update.after(TrackedProgressSet)
+                                      .run_if(in_state(MyAppState::LoadingScreen))))
+            .insert_state(AppCursorState::Free)
+            .add_systems(OnEnter(MyAppState::LoadingScreen), (loading_game_assets_enter, setup_camera))
+            .add_systems(OnExit(MyAppState::LoadingScreen), loading_game_assets_exit);
+        #[cfg(target_family = "wasm")]
+        app.add_plugins(web::WebPlugin);
+    }
+}
+
+/// The player's body standing at `spawn`: everything that moves it, and nothing to see it by.
+fn player_body(spawn: Option<Res<PlayerSpawn>>) -> impl Bundle {
+    let spawn = spawn.map_or(Vec3::Y * PLAYER_HALF_HEIGHT, |spawn| spawn.0);
+    // Swapped for the saved bindings once the settings have loaded
+    let input_map = default_input_map();
+    (
+        SpatialBundle::from_transform(Transform::from_translation(spawn)
+            .looking_at(spawn + Vec3::X, Vec3::Y)),
+        CharacterControllerBundle::new(Collider::capsule(1.0, 0.4), InputManagerBundle::with_map(input_map))
+            .with_movement(MovementSettings { max_speed: SPEED, ..default() }, 4.0, PI * 0.25),
+        Crouch::new(Collider::capsule(1.0, 0.4), Collider::capsule(0.3, 0.4)),
+        Stamina::default(),
+    )
+}
+
+fn spawn_player_body(mut commands: Commands, spawn: Option<Res<PlayerSpawn>>) {
+    commands.spawn(player_body(spawn));
+}
+
+fn setup_camera(mut commands: Commands,
+                mut meshes: ResMut<Assets<Mesh>>,
+                mut materials: ResMut<Assets<StandardMaterial>>,
+                spawn: Option<Res<PlayerSpawn>>) {
+    commands.spawn(player_body(spawn)).with_children(|parent| {
+        parent.spawn((PbrBundle {
+            mesh: meshes.add(Capsule3d::new(0.4, PLAYER_MODEL_HEIGHT - 0.8)),
+            material: materials.add(Color::rgb_u8(150, 40, 40)),
+            ..default()
+        }, PlayerModel));
+    });
+    commands.spawn((Camera3dBundle::default(), CameraPitch::default(), PlayerCamera::default()));
 }
 
 
 
 fn game_update_top_ui(mut contexts: EguiContexts,
-                      mut next_state: ResMut<NextState<MyGameState>>,
-                      mut day_timer: Local<DayTimer>,
-                      time: Res<Time>,
+                      mut game_commands: EventWriter<GameCommand>,
+                      mut day_timer: ResMut<DayTimer>,
+                      q_stamina: Query<&Stamina>,
                       sprites: Res<TopUISprites>,
+                      settings: Res<Persistent<Settings>>,
                       state: Res<State<MyGameState>>, ) {
     let screen = &contexts.ctx_mut().screen_rect();
-    let time_factor = day_timer.timefactor;
-    day_timer.stopwatch.tick(time.delta() / time_factor as u32);
-    day_timer.sleepwatch.tick(time.delta() / time_factor as u32);
-    day_timer.solar_pos.x = (day_timer.stopwatch.elapsed_secs()).cos() * screen.width() / 8.0;
-    day_timer.solar_pos.y = (day_timer.stopwatch.elapsed_secs()).sin() * screen.height() / TOP_UI_HEIGHT_FRACTION;
-    egui::TopBottomPanel::top("nav_panel").exact_height(screen.height() / TOP_UI_HEIGHT_FRACTION).show(contexts.ctx_mut(), |ui| {
+    // The screen rect shrinks as the UI scales up, so this keeps the panel in step with the icons in it
+    let panel_height = screen.height() / TOP_UI_HEIGHT_FRACTION * settings.accessibility.ui_scale;
+    let is_day = (day_timer.stopwatch.elapsed_secs() / PI) as u16 % 2 == 0;
+    let sky_angle = match (settings.accessibility.reduced_motion, is_day) {
+        (false, _) => day_timer.stopwatch.elapsed_secs(),
+        (true, true) => PINNED_SKY_ANGLE,
+        (true, false) => PINNED_SKY_ANGLE + PI,
+    };
+    day_timer.solar_pos.x = sky_angle.cos() * screen.width() / 8.0;
+    day_timer.solar_pos.y = sky_angle.sin() * panel_height;
+    egui::TopBottomPanel::top("nav_panel").exact_height(panel_height).show(contexts.ctx_mut(), |ui| {
         ui.label(format!("You are on day # {0}", (day_timer.stopwatch.elapsed_secs() / (2.0 * PI)) as u16 + 1));
+        if let Ok(stamina) = q_stamina.get_single() {
+            ui.add(egui::ProgressBar::new(stamina.fraction()).desired_width(screen.width() / 8.0).text("Stamina"));
+        }
         match state.get() {
             MyGameState::Outdoors => {
                 if ui.button("Go Home").clicked() {
-                    next_state.set(MyGameState::Indoors);
+                    game_commands.send(GameCommand::GoHome);
                 }
             }
             MyGameState::Indoors => {
                 if ui.button("Go to Work").clicked() {
-                    next_state.set(MyGameState::Outdoors);
+                    game_commands.send(GameCommand::GoToWork);
                 }
                 if ui.button("Go to Sleep").clicked() {
-                    next_state.set(MyGameState::Sleeping);
-                    day_timer.timefactor /= TIME_FACTOR;
-                    day_timer.sleepwatch.reset();
-                }
-            }
-            MyGameState::Sleeping => {
-                day_timer.sleepwatch.tick(time.delta());
-                if ((day_timer.sleepwatch.elapsed_secs() / PI) as u16 > 1) {
-                    day_timer.sleepwatch.reset();
-                    day_timer.timefactor *= TIME_FACTOR;
-                    next_state.set(MyGameState::Indoors);
+                    game_commands.send(GameCommand::GoToSleep);
                 }
             }
+            MyGameState::Sleeping => {}
         }
-        if (day_timer.stopwatch.elapsed_secs() / PI) as u16 % 2 == 0 {
+        if is_day {
             egui::widgets::Image::new(egui::load::SizedTexture::new(
                 sprites.solar.id.unwrap(),
                 SOLAR_ICON_SIZE,
-            )).paint_at(ui, egui::Rect::from_center_size(day_timer.solar_pos.mul(-1.0).add(egui::Vec2 { x: screen.width() / 2.0, y: screen.height() / TOP_UI_HEIGHT_FRACTION }), SOLAR_ICON_SIZE));
+            )).paint_at(ui, egui::Rect::from_center_size(day_timer.solar_pos.mul(-1.0).add(egui::Vec2 { x: screen.width() / 2.0, y: panel_height }), SOLAR_ICON_SIZE));
         } else {
             egui::widgets::Image::new(egui::load::SizedTexture::new(
                 sprites.lunar.id.unwrap(),
                 SOLAR_ICON_SIZE.div(egui::Vec2 { x: 2.0, y: 2.0 }),
-            )).paint_at(ui, egui::Rect::from_center_size(day_timer.solar_pos.add(egui::Vec2 { x: screen.width() / 2.0, y: screen.height() / TOP_UI_HEIGHT_FRACTION }), LUNAR_ICON_SIZE));
+            )).paint_at(ui, egui::Rect::from_center_size(day_timer.solar_pos.add(egui::Vec2 { x: screen.width() / 2.0, y: panel_height }), LUNAR_ICON_SIZE));
         }
         if day_timer.person_state.special_emoji.is_some() {
             egui::widgets::Image::new(egui::load::SizedTexture::new(
                 sprites.special_emoji_map.get(&day_timer.person_state.special_emoji.clone().unwrap()).unwrap().id.unwrap(),
                 SOLAR_ICON_SIZE,
-            )).paint_at(ui, egui::Rect::from_center_size(egui::Pos2 { x: screen.width() / 2.0, y: screen.height() / TOP_UI_HEIGHT_FRACTION - (SOLAR_ICON_SIZE.y / 2.0) }, SOLAR_ICON_SIZE));
+            )).paint_at(ui, egui::Rect::from_center_size(egui::Pos2 { x: screen.width() / 2.0, y: panel_height - (SOLAR_ICON_SIZE.y / 2.0) }, SOLAR_ICON_SIZE));
         } else {
             egui::widgets::Image::new(egui::load::SizedTexture::new(
                 sprites.emoji_map.get(&day_timer.person_state.emoji).unwrap().id.unwrap(),
                 SOLAR_ICON_SIZE,
-            )).paint_at(ui, egui::Rect::from_center_size(egui::Pos2 { x: screen.width() / 2.0, y: screen.height() / TOP_UI_HEIGHT_FRACTION - (SOLAR_ICON_SIZE.y / 2.0) }, SOLAR_ICON_SIZE));
+            )).paint_at(ui, egui::Rect::from_center_size(egui::Pos2 { x: screen.width() / 2.0, y: panel_height - (SOLAR_ICON_SIZE.y / 2.0) }, SOLAR_ICON_SIZE));
         }
     });
 }
 
-fn loading_game_update(mut commands: Commands,
-                       mut contexts: EguiContexts,
-                       counter: Res<ProgressCounter>,
-                       loading: Res<AssetsLoading>, ) {
+fn loading_game_update(mut contexts: EguiContexts,
+                       counter: Res<ProgressCounter>, ) {
     let progress = counter.progress();
     egui::Window::new("Loading").show(contexts.ctx_mut(), |ui| {
         ui.label(format!("Loading...{0}/{1}", progress.done, progress.total));
     });
-    let config_dir = dirs::config_dir()
-        .map(|native_config_dir| native_config_dir.join("dracula"))
-        .unwrap_or(Path::new("local").join("configuration"));
-    commands.insert_resource(
-        Persistent::<Settings>::builder()
-            .name("settings")
-            .format(StorageFormat::Toml)
-            .path(config_dir.join("dracula_settings.toml"))
-            .default(Settings { mouse_sensitivity: Vec2 { x: 1.0, y: 1.0 }, look_sensitivity: 50.0 })
-            .build()
-            .expect("failed to initialize settings")
-    );
-}
-
-fn load_room(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut materials: ResMut<Assets<StandardMaterial>>, query: Query<Entity, With<BuildingMarker>>) {
-    for entity in query.iter() {
-        commands.entity(entity).despawn();
+}
+
+impl BuildingLayout {
+    /// Names the building laid out from `seed`, the same for every launch that lays it out again.
+    pub fn identity(&self, seed: u64) -> String {
+        match self {
+            BuildingLayout::House => format!("house {seed:016x}"),
+            BuildingLayout::Castle => format!("castle {seed:016x}"),
+            // Drawn by hand, so the seed makes no difference
+            BuildingLayout::FloorPlan(name) => format!("floor plan {name}"),
+        }
     }
-    let specs = vec![
+
+    /// Lays the building out. Only floor plans can fail, when their file is missing or invalid.
+    pub fn generate(&self, rng: &mut impl Rng) -> Result<BuildingChunk, FloorPlanError> {
+        match self {
+            BuildingLayout::House => Ok(generate_building(house_specs(), rng)),
+            BuildingLayout::Castle => Ok(generate_castle(&CastleParameters::default(), rng)),
+            BuildingLayout::FloorPlan(name) => load_floor_plan(Path::new("assets/floor_plans").join(format!("{name}.txt"))),
+        }
+    }
+}
+
+fn house_specs() -> Vec<(BuildingIterationParameters, usize)> {
+    vec![
         (BuildingIterationParameters {
             aspect_ratio_probability_factor: 0.3,
             aspect_ratio_probability_offset: 1.0,
             min_rooms_in_split: 2,
             max_rooms_in_split: 4,
             is_hallway: true,
+            hallway_width: HALL_WIDTH,
+            hallway_branching: 0.6,
             room_requirements: vec![
                 RoomSpec {
                     area_range: Rangef::new(3.0, 30.0),
@@ -298,6 +572,8 @@ fn load_room(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut mater
             min_rooms_in_split: 2,
             max_rooms_in_split: 3,
             is_hallway: false,
+            hallway_width: HALL_WIDTH,
+            hallway_branching: 0.0,
             room_requirements: vec![
                 RoomSpec {
                     area_range: Rangef::new(3.0, 30.0),
@@ -309,105 +585,159 @@ fn load_room(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut mater
                     has_direct_access: false,
                     ..Default::default()
                 }, ],
-        }, 4)];
+        }, 4)]
+}
 
-    let mut building = generate_building(specs);
+/// Lays out the building from the game's seed, so it's the same building every time the player comes
+/// back, and its doors find their saved lock state again.
+fn load_room(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut materials: ResMut<Assets<StandardMaterial>>, layout: Res<BuildingLayout>, rng: Res<GameRng>, lock_save: Res<Persistent<LockSave>>, query: Query<Entity, With<BuildingMarker>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+    let mut building_rng = StdRng::seed_from_u64(rng.seed());
+    let (building, building_id) = match layout.generate(&mut building_rng) {
+        Ok(building) => (building, layout.identity(rng.seed())),
+        Err(error) => {
+            error!("failed to lay out {layout:?} ({error}), building a house instead");
+            (generate_building(house_specs(), &mut building_rng), BuildingLayout::House.identity(rng.seed()))
+        }
+    };
+    if let Some(room) = building.spawn_room() {
+        let centre = room.rect.center();
+        commands.insert_resource(PlayerSpawn(Vec3::new(centre.x, room.floor_height() + PLAYER_HALF_HEIGHT, centre.y)));
+    }
+    // Ground all around, just under the floors, for the player to walk out onto
+    let ground = Vec3::new(building.rect.width() + 2.0 * GROUND_MARGIN, GROUND_THICKNESS, building.rect.height() + 2.0 * GROUND_MARGIN);
+    commands.spawn((PbrBundle {
+        mesh: meshes.add(Cuboid::from_size(ground)),
+        material: materials.add(Color::rgb_u8(70, 90, 60)),
+        transform: Transform::from_xyz(building.rect.center().x, -0.01 - GROUND_THICKNESS / 2.0, building.rect.center().y),
+        ..default()
+    },
+                    Collider::cuboid(ground.x, ground.y, ground.z),
+                    BuildingMarker,
+                    RigidBody::Static,
+                    CollisionLayers::new(GameLayer::Environment, [GameLayer::Environment, GameLayer::Player])));
+    commands.insert_resource(BuildingBounds(Rect::new(building.rect.min.x, building.rect.min.y, building.rect.max.x, building.rect.max.y)));
+    let openings = building.door_openings();
     let all = building.get_all();
     for chunk_index in 0..all.len() {
         let chunk = all[chunk_index];
         let room = chunk.rect;
         if let Some(BuildingChunkData::Parent(children, is_hallway)) = &chunk.divided_chunks {} else {
+            for wall in chunk.wall_boxes(&openings) {
+                commands.spawn((PbrBundle {
+                    mesh: meshes.add(Cuboid::from_size(wall.size)),
+                    material: materials.add(Color::rgb_u8(124, 144, 255)),
+                    transform: wall.transform,
+                    ..default()
+                },
+                                Collider::cuboid(wall.size.x, wall.size.y, wall.size.z),
+                                BuildingMarker,
+                                RigidBody::Static,
+                                CollisionLayers::new(GameLayer::Environment, [GameLayer::Environment, GameLayer::Player])));
+            }
+            let floor = chunk.floor_mesh();
             commands.spawn((PbrBundle {
-                mesh: meshes.add(Cuboid::new(room.width(), 1.0, 0.1)),
-                material: materials.add(Color::rgb_u8(124, 144, 255)),
-                transform: Transform::from_xyz(room.center_bottom().x, 0.5, room.center_bottom().y),
-                ..default()
-            },
-                            Collider::cuboid(room.width(), 1.0, 0.1),
-                            BuildingMarker,
-                            RigidBody::Static,
-                            CollisionLayers::new(GameLayer::Environment, [GameLayer::Environment, GameLayer::Player])));
-            commands.spawn((PbrBundle {
-                mesh: meshes.add(Cuboid::new(room.width(), 1.0, 0.1)),
-                material: materials.add(Color::rgb_u8(124, 144, 255)),
-                transform: Transform::from_xyz(room.center_top().x, 0.5, room.center_top().y),
-                ..default()
-            },
-                            BuildingMarker,
-                            RigidBody::Static,
-                            Collider::cuboid(room.width(), 1.0, 0.1),
-                            CollisionLayers::new(GameLayer::Environment, [GameLayer::Environment, GameLayer::Player])));
-            commands.spawn((PbrBundle {
-                mesh: meshes.add(Cuboid::new(0.1, 1.0, room.height())),
-                material: materials.add(Color::rgb_u8(124, 144, 255)),
-                transform: Transform::from_xyz(room.right_center().x, 0.5, room.right_center().y),
+                mesh: meshes.add(floor.clone()),
+                material: materials.add(Color::rgb_u8(90, 80, 70)),
                 ..default()
             },
-                            Collider::cuboid(0.1, 1.0, room.height()),
+                            Collider::trimesh_from_mesh(&floor).unwrap(),
                             BuildingMarker,
                             RigidBody::Static,
                             CollisionLayers::new(GameLayer::Environment, [GameLayer::Environment, GameLayer::Player])));
-            commands.spawn((PbrBundle {
-                mesh: meshes.add(Cuboid::new(0.1, 1.0, room.height())),
-                material: materials.add(Color::rgb_u8(124, 144, 255)),
-                transform: Transform::from_xyz(room.left_center().x, 0.5, room.left_center().y),
-                ..default()
-            },
-                            Collider::cuboid(0.1, 1.0, room.height()),
-                            BuildingMarker,
-                            RigidBody::Static,
-                            CollisionLayers::new(GameLayer::Environment, [GameLayer::Environment, GameLayer::Player]),
-            ));
-            commands.spawn(PointLightBundle {
-                point_light: PointLight {
-                    color: Color::rgb(1.0, 1.0, 1.0),
-                    intensity: 10000.0,
-                    range: room.width().max(room.height()),
-                    radius: 0.0,
-                    ..Default::default()
+            for merlon in chunk.merlon_boxes() {
+                commands.spawn((PbrBundle {
+                    mesh: meshes.add(Cuboid::from_size(merlon.size)),
+                    material: materials.add(Color::rgb_u8(110, 110, 130)),
+                    transform: merlon.transform,
+                    ..default()
+                },
+                                BuildingMarker));
+            }
+            for tag in &chunk.keys {
+                let key = Key { name: format!("{tag} key"), door: room_key_id(&building_id, tag) };
+                if lock_save.keys.contains(&key) {
+                    continue;
+                }
+                commands.spawn((PbrBundle {
+                    mesh: meshes.add(Cuboid::new(0.25, 0.05, 0.1)),
+                    material: materials.add(Color::rgb_u8(212, 175, 55)),
+                    transform: Transform::from_xyz(room.center().x, chunk.floor_height() + 0.05, room.center().y),
+                    ..default()
                 },
-                transform: Transform::from_xyz(room.center().x, 1.0, room.center().y),
-                ..Default::default()
-            });
-            for dir in 0..chunk.doors.len() {
-                let angle = dir as f32 * PI / 2.0;
-                let angle_vec = Vec2::from_angle(angle);
-                let corner_pos = room.center().add(bevy_egui::egui::emath::Vec2 { x: (angle_vec.x * room.width() / 2.0), y: (angle_vec.y * room.height() / 2.0) });
-                for door_num in 0..chunk.doors[dir].len() {
-                    let mut door_transform = if (angle_vec.y != 0.0) {
-                        Transform::from_xyz(corner_pos.x - (room.width() / 2.0) + ((room.width() / (chunk.doors[dir].len()) as f32) * ((door_num) as f32 + 0.5)), 0.5, corner_pos.y)
-                    } else {
-                        Transform::from_xyz(corner_pos.x, 0.5, corner_pos.y - (room.height() / 2.0) + ((room.height() / (chunk.doors[dir].len()) as f32) * ((door_num) as f32 + 0.5)))
-                    };
-                    door_transform.rotate_local_y(angle);
-                    let id = format!("door@{0},{1},{2}", chunk_index, dir, door_num);
-                    commands.spawn((PbrBundle {
-                        mesh: meshes.add(Cuboid::new(0.11, 1.1, DOOR_WIDTH)),
-                        material: materials.add(Color::rgb_u8(124, 255, 124)),
-                        transform: door_transform,
-                        ..default()
+                                KeyPickup(key),
+                                BuildingMarker));
+            }
+            if chunk.kind != RoomKind::Courtyard {
+                commands.spawn((PointLightBundle {
+                    point_light: PointLight {
+                        color: Color::rgb(1.0, 1.0, 1.0),
+                        intensity: 10000.0,
+                        range: room.width().max(room.height()),
+                        radius: 0.0,
+                        ..Default::default()
                     },
-                                    CollisionLayers::new(GameLayer::RaycastInteractible, [GameLayer::Environment, GameLayer::Player]),
-                                    BuildingMarker,
-                                    RigidBody::Static,
-                                    Collider::cuboid(0.11, 1.1, DOOR_WIDTH),
-                                    DoorEguiInteractableEmpty{id:id,window_open:true, door_enum: chunk.doors[dir][door_num] }
-                    ));
+                    transform: Transform::from_xyz(room.center().x, chunk.floor_height() + 1.0, room.center().y),
+                    ..Default::default()
+                },
+                                BuildingMarker));
+            }
+            for (dir, door_num, door_enum, door_transform) in chunk.door_transforms() {
+                let id = format!("{building_id}/door@{chunk_index},{dir},{door_num}");
+                let outward = Vec2::from_angle(dir as f32 * PI / 2.0);
+                let inside = Vec3::new(-outward.x, 0.0, -outward.y);
+                let key_id = match (door_enum, &chunk.tag) {
+                    (DoorEnum::Exterior, _) => FRONT_DOOR_KEY.to_string(),
+                    (_, Some(tag)) => room_key_id(&building_id, tag),
+                    (_, None) => id.clone(),
+                };
+                let lock = DoorLock { key_id, inside };
+                let locked = lock_save.doors.get(&id).copied().unwrap_or(matches!(door_enum, DoorEnum::Interior(true)));
+                let mut door = commands.spawn((PbrBundle {
+                    mesh: meshes.add(Cuboid::new(0.11, DOOR_HEIGHT, DOOR_WIDTH)),
+                    material: materials.add(Color::rgb_u8(124, 255, 124)),
+                    transform: door_transform,
+                    ..default()
+                },
+                                CollisionLayers::new(GameLayer::RaycastInteractible, [GameLayer::Environment, GameLayer::Player]),
+                                BuildingMarker,
+                                RigidBody::Kinematic,
+                                Collider::cuboid(0.11, DOOR_HEIGHT, DOOR_WIDTH),
+                                DoorEguiInteractableEmpty{id,window_open:true, door_enum },
+                                InteractPrompt::default(),
+                                lock,
+                                if locked { DoorState::Locked } else { DoorState::Closed },
+                ));
+                // Stairs have no opening cut for them to swing through
+                if !matches!(door_enum, DoorEnum::Stairs(_)) {
+                    door.insert(DoorHinge::new(door_transform, inside));
                 }
             }
         }
     }
 }
 
-use crate::building::{BuildingChunkData, BuildingIterationParameters, DoorEnum, generate_building, HALL_WIDTH, RoomSpec};
+use crate::building::{BuildingChunk, BuildingChunkData, BuildingIterationParameters, DOOR_HEIGHT, DoorEnum, generate_building, HALL_WIDTH, RoomKind, RoomSpec};
+use crate::castle::{CastleParameters, generate_castle};
+use crate::floor_plan::{FloorPlanError, load_floor_plan};
+use crate::door::{DoorHinge, DoorPlugin, DoorState};
+use crate::interactable::{InteractablePlugin, InteractPrompt};
+use crate::lock::{DoorLock, FRONT_DOOR_KEY, Key, KeyPickup, LockPlugin, LockSave, room_key_id};
+use crate::locomotion::MovementSettings;
+use crate::replay::{InputRecording, RecordPlugin, Replay, ReplayPlugin};
+use crate::storage::temporary_storage;
+use crate::player_camera::{BuildingBounds, PLAYER_MODEL_HEIGHT, PlayerCamera, PlayerCameraPlugin, PlayerModel};
+use crate::accessibility::{AccessibilityPlugin, AccessibilitySettings};
+use crate::controls::{ControlsPlugin, default_input_map, GamepadSettings};
+use crate::settings::{SETTINGS_VERSION, SettingsPlugin};
+use crate::simulation::{GameCommand, SimulationPlugin, SimulationSet};
 
 fn loading_game_assets_enter(mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
                              mut commands: Commands,
                              asset_server: Res<AssetServer>,
-                             mut loading: ResMut<AssetsLoading>,
                              mut egui_user_textures: ResMut<EguiUserTextures>,
-                             mut meshes: ResMut<Assets<Mesh>>,
-                             mut materials: ResMut<Assets<StandardMaterial>>,
                              mut config_store: ResMut<GizmoConfigStore>,
 ) {
     for (_, config, _) in config_store.iter_mut() {
@@ -422,17 +752,13 @@ fn loading_game_assets_enter(mut q_windows: Query<&mut Window, With<PrimaryWindo
     let mut special_emojis: HashMap<SpecialEmoji, BevyEguiImageWrapper> = HashMap::new();
     for emoji in Emoji::iter() {
         let handle: Handle<Image> = asset_server.load(format!("emojis/{0}.png", &emoji.to_string()));
-        loading.add(&handle);
         emojis.insert(emoji, BevyEguiImageWrapper { id: None, handle });
     }
     for emoji in SpecialEmoji::iter() {
         let handle: Handle<Image> = asset_server.load(format!("emojis/special/{0}.png", &emoji.to_string()));
-        loading.add(&handle);
         special_emojis.insert(emoji, BevyEguiImageWrapper { id: None, handle });
     }
 
-    loading.add(&solar_handle);
-    loading.add(&lunar_handle);
     // emoji_handle. .typed::<T>()
     commands.insert_resource(TopUISprites {
         solar: BevyEguiImageWrapper { id: None, handle: solar_handle },
@@ -463,7 +789,18 @@ fn loading_game_assets_enter(mut q_windows: Query<&mut Window, With<PrimaryWindo
             .into(),
         ..default()
     });
-    setup_camera(commands);
+}
+
+/// Holds the loading screen until every image in the top panel has loaded.
+fn top_ui_sprites_progress(sprites: Option<Res<TopUISprites>>, asset_server: Res<AssetServer>) -> Progress {
+    let Some(sprites) = sprites else {
+        return false.into();
+    };
+    [&sprites.solar, &sprites.lunar].into_iter()
+        .chain(sprites.emoji_map.values())
+        .chain(sprites.special_emoji_map.values())
+        .map(|image| Progress::from(asset_server.is_loaded_with_dependencies(&image.handle)))
+        .fold(Progress::default(), Add::add)
 }
 
 fn loading_game_assets_exit(mut sprites: ResMut<TopUISprites>,
@@ -482,11 +819,18 @@ fn loading_game_assets_exit(mut sprites: ResMut<TopUISprites>,
 
 fn main_menu_gui_system(mut app_exit_events: ResMut<Events<bevy::app::AppExit>>,
                         mut contexts: EguiContexts,
-                        mut state: ResMut<NextState<MyAppState>>,
+                        mut game_commands: EventWriter<GameCommand>,
+                        mut layout: ResMut<BuildingLayout>,
 ) {
     egui::CentralPanel::default().show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.selectable_value(layout.as_mut(), BuildingLayout::House, "House");
+            ui.selectable_value(layout.as_mut(), BuildingLayout::Castle, "Castle");
+            ui.selectable_value(layout.as_mut(), BuildingLayout::FloorPlan("harker_office".to_string()), "Harker's office");
+            ui.selectable_value(layout.as_mut(), BuildingLayout::FloorPlan("count_study".to_string()), "The Count's study");
+        });
         if ui.button("Start").clicked() {
-            state.set(MyAppState::LoadingScreen)
+            game_commands.send(GameCommand::StartGame);
         }
         if ui.button("Exit").clicked() {
             app_exit_events.send(bevy::app::AppExit);
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_persistent::prelude::*;
+use bevy_persistent::storage::Storage;
+use serde::{Deserialize, Serialize};
+
+use crate::building::STOREY_HEIGHT;
+use crate::door::DoorState;
+use crate::storage::{back_up, data_storage, temporary_storage};
+use crate::kinematic_character_3d::{CharacterController, DoorEguiInteractableComponent, DoorEguiInteractableEmpty};
+
+/// Key id shared by the exterior doors of the player's house.
+pub const FRONT_DOOR_KEY: &str = "front door";
+const LOCKPICK_TIME: Duration = Duration::from_secs(6);
+const LOCKS_NAME: &str = "door locks";
+const LOCKS_FILE: &str = "door_locks.ron";
+/// How close the player has to come to a key to pick it up.
+const KEY_REACH: f32 = 1.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Key {
+    pub name: String,
+    /// The [`DoorLock::key_id`] this key fits.
+    pub door: String,
+}
+
+/// Lock state of every door the player has touched, along with the keys they carry.
+#[derive(Resource, Serialize, Deserialize)]
+pub struct LockSave {
+    /// Keyed by door id, which starts with the identity of the building the door is in.
+    pub doors: HashMap<String, bool>,
+    pub keys: Vec<Key>,
+}
+
+impl Default for LockSave {
+    fn default() -> Self {
+        Self {
+            doors: HashMap::new(),
+            keys: vec![Key { name: "House key".to_string(), door: FRONT_DOOR_KEY.to_string() }],
+        }
+    }
+}
+
+/// Key id of the doors belonging to a tagged room, which any key to the room fits.
+pub fn room_key_id(building_id: &str, tag: &str) -> String {
+    format!("{building_id}/{tag}")
+}
+
+impl LockSave {
+    pub fn key_for(&self, lock: &DoorLock) -> Option<&Key> {
+        self.keys.iter().find(|key| key.door == lock.key_id)
+    }
+}
+
+/// What it takes to lock or unlock a door, the lock state itself is its [`DoorState`].
+#[derive(Component, Debug, Clone)]
+pub struct DoorLock {
+    pub key_id: String,
+    /// Points from the door into the room it belongs to, the side it can be locked from without a key.
+    pub inside: Vec3,
+}
+
+impl DoorLock {
+    pub fn is_inside(&self, door: Vec3, position: Vec3) -> bool {
+        (position - door).dot(self.inside) > 0.0
+    }
+}
+
+/// A key lying in a room, taken when the player walks up to it.
+#[derive(Component, Debug, Clone)]
+pub struct KeyPickup(pub Key);
+
+/// A lockpicking attempt in progress on a door, given up if the door window closes.
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct LockPicking(pub Timer);
+
+impl Default for LockPicking {
+    fn default() -> Self {
+        Self(Timer::new(LOCKPICK_TIME, TimerMode::Once))
+    }
+}
+
+/// Loads the saved locks from `storage`. A file that can't be read is backed up and replaced with the
+/// defaults, the same as the settings, so a bad save never stops the game.
+pub fn load_door_locks(storage: Storage) -> Persistent<LockSave> {
+    if storage.occupied() {
+        if let Err(error) = storage.read::<LockSave>(LOCKS_NAME, StorageFormat::Ron) {
+            match back_up(&storage) {
+                Some(backup) => warn!("door locks couldn't be read ({error}), starting over, the old file was kept at {backup}"),
+                None => warn!("door locks couldn't be read ({error}), starting over"),
+            }
+        }
+    }
+    Persistent::new(LOCKS_NAME, StorageFormat::Ron, storage.clone(), true, LockSave::default(), true, true)
+        .unwrap_or_else(|error| {
+            error!("door locks can't be saved to {storage} ({error}), changes will be lost on exit");
+            Persistent::new(LOCKS_NAME, StorageFormat::Ron, temporary_storage(LOCKS_FILE), true, LockSave::default(), true, true)
+                .expect("failed to initialize door locks in a temporary location")
+        })
+}
+
+/// Loads the saved locks once, when the app is built.
+pub struct LockPlugin {
+    pub storage: Storage,
+}
+
+impl Default for LockPlugin {
+    fn default() -> Self {
+        Self { storage: data_storage(LOCKS_FILE) }
+    }
+}
+
+impl Plugin for LockPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_door_locks(self.storage.clone()))
+            .add_systems(Update, (pick_up_keys, lockpicking, save_door_locks).chain());
+    }
+}
+
+fn pick_up_keys(mut commands: Commands,
+                mut lock_save: ResMut<Persistent<LockSave>>,
+                q_player: Query<&Transform, With<CharacterController>>,
+                q_keys: Query<(Entity, &KeyPickup, &Transform)>) {
+    let Ok(player) = q_player.get_single() else {
+        return;
+    };
+    let mut picked_up = false;
+    for (entity, pickup, transform) in &q_keys {
+        let offset = transform.translation - player.translation;
+        if offset.xz().length() > KEY_REACH || offset.y.abs() > STOREY_HEIGHT / 2.0 {
+            continue;
+        }
+        info!("picked up the {}", pickup.0.name);
+        if !lock_save.keys.contains(&pickup.0) {
+            lock_save.keys.push(pickup.0.clone());
+            picked_up = true;
+        }
+        commands.entity(entity).despawn();
+    }
+    if picked_up {
+        if let Err(error) = lock_save.persist() {
+            error!("failed to save door locks: {error}");
+        }
+    }
+}
+
+fn lockpicking(mut commands: Commands,
+               time: Res<Time>,
+               mut q_picking: Query<(Entity, &mut LockPicking, &mut DoorState, Has<DoorEguiInteractableComponent>)>) {
+    for (entity, mut picking, mut state, window_open) in &mut q_picking {
+        if !window_open {
+            commands.entity(entity).remove::<LockPicking>();
+            continue;
+        }
+        if picking.0.tick(time.delta()).just_finished() {
+            *state = DoorState::Closed;
+            commands.entity(entity).remove::<LockPicking>();
+        }
+    }
+}
+
+fn save_door_locks(mut lock_save: ResMut<Persistent<LockSave>>,
+                   q_doors: Query<(&DoorEguiInteractableEmpty, Ref<DoorState>), Changed<DoorState>>) {
+    let mut changed = false;
+    for (door, state) in &q_doors {
+        // Doors are spawned with their saved state, that's not a change worth writing
+        if state.is_added() {
+            continue;
+        }
+        let locked = *state == DoorState::Locked;
+        if lock_save.doors.get(&door.id) != Some(&locked) {
+            lock_save.doors.insert(door.id.clone(), locked);
+            changed = true;
+        }
+    }
+    if !changed {
+        return;
+    }
+    if let Err(error) = lock_save.persist() {
+        error!("failed to save door locks: {error}");
+    }
+}
@@ -0,0 +1,43 @@
+//! Horizontal acceleration and friction for walking characters.
+//!
+//! Everything here is written against the time step it's given, so a character covers the same
+//! ground whether it's ticked 30 or 240 times a second.
+
+use bevy::prelude::*;
+
+/// How a character speeds up towards, and slows down from, its walking speed.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct MovementSettings {
+    /// Units per second squared gained while heading towards the wished velocity.
+    pub acceleration: f32,
+    /// Walking speed, in units per second, before sprinting, crouching and the player's needs are applied.
+    pub max_speed: f32,
+    /// How quickly speed dies away with no input, as an exponential decay rate per second.
+    pub friction: f32,
+}
+
+impl Default for MovementSettings {
+    fn default() -> Self {
+        Self { acceleration: 40.0, max_speed: 5.0, friction: 10.0 }
+    }
+}
+
+/// Steps the horizontal part of `velocity` by `delta` seconds, towards `wish` (a direction no longer
+/// than one) at `max_speed * speed_factor`. The vertical part is left alone.
+pub fn accelerate(velocity: Vec3, wish: Vec3, settings: &MovementSettings, speed_factor: f32, delta: f32) -> Vec3 {
+    let horizontal = Vec3::new(velocity.x, 0.0, velocity.z);
+    let target = Vec3::new(wish.x, 0.0, wish.z).clamp_length_max(1.0) * settings.max_speed * speed_factor;
+    let next = if target == Vec3::ZERO {
+        // Exponential decay comes out the same however the time is sliced up
+        horizontal * (-settings.friction * delta).exp()
+    } else {
+        let difference = target - horizontal;
+        let step = settings.acceleration * delta;
+        if difference.length() <= step {
+            target
+        } else {
+            horizontal + difference.normalize() * step
+        }
+    };
+    Vec3::new(next.x, velocity.y, next.z)
+}
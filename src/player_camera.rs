@@ -0,0 +1,157 @@
+use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
+use bevy::transform::TransformSystem;
+use bevy_xpbd_3d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use crate::building::STOREY_HEIGHT;
+use crate::kinematic_character_3d::{CameraPitch, CharacterController, GameLayer, PlayerMovement};
+use crate::states::MyAppState;
+
+/// Height of the third person pivot above the middle of the body.
+const SHOULDER_HEIGHT: f32 = 0.6;
+/// How far the third person camera sits to the right of the body.
+const SHOULDER_OFFSET: f32 = 0.5;
+const THIRD_PERSON_DISTANCE: f32 = 3.0;
+/// Radius of the sphere cast towards the third person camera, so walls don't clip the near plane.
+const CAMERA_RADIUS: f32 = 0.2;
+/// Just under the storey above, so the top down view cuts the building off at the player's floor.
+const TOP_DOWN_HEIGHT: f32 = STOREY_HEIGHT * 0.95;
+/// Space left around the building in the top down view.
+const TOP_DOWN_MARGIN: f32 = 2.0;
+/// Width of the top down view when there's no building to frame.
+const TOP_DOWN_DEFAULT_EXTENT: f32 = 20.0;
+/// Height of the standing player model, matching the standing collider.
+pub const PLAYER_MODEL_HEIGHT: f32 = 1.8;
+
+/// How the player's camera follows the body.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, strum_macros::Display)]
+pub enum CameraMode {
+    #[default]
+    FirstPerson,
+    /// Over the right shoulder, pulled in when there's a wall behind the player.
+    ThirdPerson,
+    /// A floor plan of the current building, looking straight down.
+    TopDown,
+}
+
+impl CameraMode {
+    pub fn next(self) -> Self {
+        match self {
+            CameraMode::FirstPerson => CameraMode::ThirdPerson,
+            CameraMode::ThirdPerson => CameraMode::TopDown,
+            CameraMode::TopDown => CameraMode::FirstPerson,
+        }
+    }
+}
+
+/// The camera following the player's body, kept apart from it so it can leave the body's head.
+#[derive(Component, Debug)]
+pub struct PlayerCamera {
+    pub mode: CameraMode,
+    /// Vertical field of view in first and third person, in radians.
+    pub fov: f32,
+}
+
+impl Default for PlayerCamera {
+    fn default() -> Self {
+        Self { mode: CameraMode::default(), fov: PerspectiveProjection::default().fov }
+    }
+}
+
+/// The visible body of the player, hidden in first person.
+#[derive(Component)]
+pub struct PlayerModel;
+
+/// Floor plan extent of the building the player is in, on the xz plane.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct BuildingBounds(pub Rect);
+
+fn cycle_camera_mode(q_player: Query<&ActionState<PlayerMovement>, With<CharacterController>>,
+                     mut q_camera: Query<&mut PlayerCamera>) {
+    for action_state in &q_player {
+        if action_state.just_pressed(&PlayerMovement::CycleCamera) {
+            for mut camera in &mut q_camera {
+                camera.mode = camera.mode.next();
+            }
+        }
+    }
+}
+
+type PlayerQuery<'w, 's> = Query<'w, 's, (Entity, &'static Transform, &'static Collider), (With<CharacterController>, Without<PlayerCamera>)>;
+type CameraQuery<'w, 's> = Query<'w, 's, (&'static PlayerCamera, &'static CameraPitch, &'static mut Transform, &'static mut Projection),
+                                 (Without<CharacterController>, Without<PlayerModel>)>;
+type ModelQuery<'w, 's> = Query<'w, 's, (&'static mut Visibility, &'static mut Transform),
+                                (With<PlayerModel>, Without<CharacterController>, Without<PlayerCamera>)>;
+
+/// Places the camera for its mode once physics has moved the body for this frame.
+fn follow_player(spatial_query: SpatialQuery,
+                 bounds: Option<Res<BuildingBounds>>,
+                 q_player: PlayerQuery,
+                 mut q_camera: CameraQuery,
+                 mut q_model: ModelQuery) {
+    let Ok((entity, body, collider)) = q_player.get_single() else {
+        return;
+    };
+    let Ok((camera, pitch, mut transform, mut projection)) = q_camera.get_single_mut() else {
+        return;
+    };
+    let aabb = collider.shape_scaled().compute_local_aabb();
+    for (mut visibility, mut model_transform) in &mut q_model {
+        *visibility = if camera.mode == CameraMode::FirstPerson { Visibility::Hidden } else { Visibility::Inherited };
+        // Squashes along with the collider when crouching
+        model_transform.scale.y = (aabb.maxs.y - aabb.mins.y) / PLAYER_MODEL_HEIGHT;
+    }
+    let look = body.rotation * Quat::from_rotation_x(pitch.angle);
+    match camera.mode {
+        CameraMode::FirstPerson => {
+            *transform = Transform::from_translation(body.translation).with_rotation(look);
+        }
+        CameraMode::ThirdPerson => {
+            let head = body.translation + Vec3::Y * SHOULDER_HEIGHT;
+            let offset = *body.right() * SHOULDER_OFFSET + look * Vec3::Z * THIRD_PERSON_DISTANCE;
+            let mut distance = offset.length();
+            if let Ok(direction) = Direction3d::new(offset) {
+                let filter = SpatialQueryFilter::from_mask(GameLayer::Environment).with_excluded_entities([entity]);
+                if let Some(hit) = spatial_query.cast_shape(&Collider::sphere(CAMERA_RADIUS), head, Quat::IDENTITY, direction, distance, true, filter) {
+                    distance = hit.time_of_impact;
+                }
+            }
+            *transform = Transform::from_translation(head + offset.normalize_or_zero() * distance).with_rotation(look);
+        }
+        CameraMode::TopDown => {
+            let (centre, size) = match &bounds {
+                Some(bounds) => (bounds.0.center(), bounds.0.size() + Vec2::splat(TOP_DOWN_MARGIN * 2.0)),
+                None => (body.translation.xz(), Vec2::splat(TOP_DOWN_DEFAULT_EXTENT)),
+            };
+            let feet = body.translation.y + aabb.mins.y;
+            // North stays at the top of the screen, so the plan reads the same as the editor
+            *transform = Transform::from_xyz(centre.x, feet + TOP_DOWN_HEIGHT, centre.y)
+                .looking_to(Vec3::NEG_Y, Vec3::NEG_Z);
+            // Nothing above the camera is drawn, which hides the floors overhead
+            *projection = Projection::Orthographic(OrthographicProjection {
+                near: 0.0,
+                scaling_mode: ScalingMode::AutoMin { min_width: size.x, min_height: size.y },
+                ..default()
+            });
+        }
+    }
+    if camera.mode != CameraMode::TopDown && !matches!(&*projection, Projection::Perspective(perspective) if perspective.fov == camera.fov) {
+        *projection = Projection::Perspective(PerspectiveProjection { fov: camera.fov, ..default() });
+    }
+}
+
+pub struct PlayerCameraPlugin;
+
+impl Plugin for PlayerCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, cycle_camera_mode.run_if(in_state(MyAppState::InGame)))
+            .add_systems(
+                PostUpdate,
+                follow_player
+                    .after(PhysicsSet::Sync)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
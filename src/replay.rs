@@ -0,0 +1,227 @@
+//! Recording the player's input and playing it back, so a session can be reproduced exactly.
+//!
+//! A recording holds the seed of [`GameRng`], where the recorded entity started, and its
+//! [`ActionState`] on every frame along with how long that frame took. Replaying forces the same
+//! frame times and writes the recorded states over whatever the real input was, so it plays out
+//! the same with or without a window.
+
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::time::{TimeSystem, TimeUpdateStrategy};
+use bevy_xpbd_3d::prelude::{Physics, PhysicsTime, TimestepMode};
+use leafwing_input_manager::plugin::InputManagerSystem;
+use leafwing_input_manager::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::GameRng;
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Write(ron::Error),
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "could not access input recording: {error}"),
+            ReplayError::Parse(error) => write!(f, "invalid input recording: {error}"),
+            ReplayError::Write(error) => write!(f, "could not write input recording: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound(serialize = "A: Serialize", deserialize = "A: Deserialize<'de>"))]
+pub struct RecordedFrame<A: Actionlike> {
+    /// How long the frame took, which is forced on the replayed frame.
+    pub delta: Duration,
+    pub action_state: ActionState<A>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound(serialize = "A: Serialize", deserialize = "A: Deserialize<'de>"))]
+pub struct InputRecording<A: Actionlike> {
+    pub seed: u64,
+    /// Where the recorded entity was on the first frame.
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub frames: Vec<RecordedFrame<A>>,
+}
+
+impl<A: Actionlike + Serialize + DeserializeOwned> InputRecording<A> {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        ron::from_str(&std::fs::read_to_string(path).map_err(ReplayError::Io)?).map_err(ReplayError::Parse)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        let text = ron::ser::to_string_pretty(self, PrettyConfig::default()).map_err(ReplayError::Write)?;
+        std::fs::write(path, text).map_err(ReplayError::Io)
+    }
+}
+
+/// Recording and replaying input, for apps to run only while the recorded entity can move.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ReplaySet;
+
+/// Empties the fixed timestep accumulators on the first recorded or replayed frame, so however long
+/// the app took to get there can't change how many fixed steps the frames after it get.
+fn reset_fixed_clocks(fixed: &mut Time<Fixed>, physics: Option<Mut<Time<Physics>>>) {
+    let overstep = fixed.overstep();
+    fixed.discard_overstep(overstep);
+    if let Some(mut physics) = physics {
+        if let TimestepMode::Fixed { overstep, .. } = physics.timestep_mode_mut() {
+            *overstep = Duration::ZERO;
+        }
+    }
+}
+
+/// The recording in progress, started on the first frame an entity has an `ActionState<A>`.
+#[derive(Resource)]
+pub struct InputRecorder<A: Actionlike> {
+    path: PathBuf,
+    pub recording: Option<InputRecording<A>>,
+}
+
+fn record_frame<A: Actionlike>(time: Res<Time>,
+                               mut fixed: ResMut<Time<Fixed>>,
+                               physics: Option<ResMut<Time<Physics>>>,
+                               rng: Res<GameRng>,
+                               mut recorder: ResMut<InputRecorder<A>>,
+                               q_recorded: Query<(&ActionState<A>, &Transform)>) {
+    let Ok((action_state, transform)) = q_recorded.get_single() else {
+        return;
+    };
+    if recorder.recording.is_none() {
+        reset_fixed_clocks(&mut fixed, physics.map(Into::into));
+    }
+    let recording = recorder.recording.get_or_insert_with(|| InputRecording {
+        seed: rng.seed(),
+        translation: transform.translation,
+        rotation: transform.rotation,
+        frames: Vec::new(),
+    });
+    recording.frames.push(RecordedFrame { delta: time.delta(), action_state: action_state.clone() });
+}
+
+fn save_recording<A: Actionlike + Serialize + DeserializeOwned>(recorder: Res<InputRecorder<A>>) {
+    let Some(recording) = &recorder.recording else {
+        return;
+    };
+    match recording.save(&recorder.path) {
+        Ok(()) => info!("saved {} frames of input to {}", recording.frames.len(), recorder.path.display()),
+        Err(error) => error!("{error}"),
+    }
+}
+
+/// Records the input of the entity with an `ActionState<A>` and writes it to `path` when the app exits.
+pub struct RecordPlugin<A: Actionlike> {
+    pub path: PathBuf,
+    _phantom: PhantomData<A>,
+}
+
+impl<A: Actionlike> RecordPlugin<A> {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), _phantom: PhantomData }
+    }
+}
+
+impl<A: Actionlike + Serialize + DeserializeOwned> Plugin for RecordPlugin<A> {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<GameRng>()
+            .insert_resource(InputRecorder::<A> { path: self.path.clone(), recording: None })
+            .add_systems(
+                PreUpdate,
+                record_frame::<A>
+                    .in_set(ReplaySet)
+                    .after(InputManagerSystem::Update)
+                    .after(InputManagerSystem::ManualControl),
+            )
+            .add_systems(Last, save_recording::<A>.run_if(on_event::<AppExit>()));
+    }
+}
+
+/// How far through its recording a replay is.
+#[derive(Resource)]
+pub struct Replay<A: Actionlike> {
+    recording: InputRecording<A>,
+    next_frame: usize,
+}
+
+impl<A: Actionlike> Replay<A> {
+    pub fn finished(&self) -> bool {
+        self.next_frame >= self.recording.frames.len()
+    }
+}
+
+/// Replayed frames take exactly as long as they did when recorded.
+fn replay_frame_time<A: Actionlike>(replay: Res<Replay<A>>, mut strategy: ResMut<TimeUpdateStrategy>) {
+    let frames = &replay.recording.frames;
+    if let Some(frame) = frames.get(replay.next_frame.min(frames.len().saturating_sub(1))) {
+        *strategy = TimeUpdateStrategy::ManualDuration(frame.delta);
+    }
+}
+
+fn replay_frame<A: Actionlike>(mut replay: ResMut<Replay<A>>,
+                               mut fixed: ResMut<Time<Fixed>>,
+                               physics: Option<ResMut<Time<Physics>>>,
+                               mut app_exit: EventWriter<AppExit>,
+                               mut q_replayed: Query<(&mut ActionState<A>, &mut Transform)>) {
+    let Ok((mut action_state, mut transform)) = q_replayed.get_single_mut() else {
+        return;
+    };
+    if replay.next_frame == 0 {
+        reset_fixed_clocks(&mut fixed, physics.map(Into::into));
+        // Left alone when it's already there, so physics doesn't sync the body back from its transform
+        let start = Transform { translation: replay.recording.translation, rotation: replay.recording.rotation, ..*transform };
+        transform.set_if_neq(start);
+    }
+    let Some(frame) = replay.recording.frames.get(replay.next_frame) else {
+        return;
+    };
+    *action_state = frame.action_state.clone();
+    replay.next_frame += 1;
+    if replay.finished() {
+        info!("finished replaying {} frames of input", replay.next_frame);
+        app_exit.send(AppExit);
+    }
+}
+
+/// Plays a recording back over the entity with an `ActionState<A>`, then exits.
+pub struct ReplayPlugin<A: Actionlike> {
+    pub recording: InputRecording<A>,
+}
+
+impl<A: Actionlike> ReplayPlugin<A> {
+    pub fn new(recording: InputRecording<A>) -> Self {
+        Self { recording }
+    }
+}
+
+impl<A: Actionlike> Plugin for ReplayPlugin<A> {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(GameRng::new(self.recording.seed))
+            .insert_resource(Replay { recording: self.recording.clone(), next_frame: 0 })
+            .init_resource::<TimeUpdateStrategy>()
+            .add_systems(First, replay_frame_time::<A>.before(TimeSystem))
+            .add_systems(
+                PreUpdate,
+                replay_frame::<A>
+                    .in_set(ReplaySet)
+                    .in_set(InputManagerSystem::ManualControl)
+                    .after(InputManagerSystem::Update),
+            );
+    }
+}
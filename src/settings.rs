@@ -0,0 +1,123 @@
+//! Loading and saving the settings file.
+//!
+//! The file carries a version number so settings saved by an older build can be migrated forward.
+//! A file that can't be read is backed up and replaced with the defaults, and failed saves are kept
+//! in [`SettingsStatus`] for the settings window to show, so a bad file never stops the game.
+
+use bevy::prelude::*;
+use bevy_egui::egui;
+use bevy_persistent::prelude::*;
+use bevy_persistent::storage::Storage;
+use strum::IntoEnumIterator;
+
+use crate::controls::default_input_map;
+use crate::kinematic_character_3d::PlayerMovement;
+use crate::storage::{back_up, config_storage, temporary_storage};
+use crate::Settings;
+
+/// Bumped whenever existing settings files need migrating, with a step for it added to [`migrate`].
+pub const SETTINGS_VERSION: u32 = 1;
+const SETTINGS_NAME: &str = "settings";
+const SETTINGS_FILE: &str = "dracula_settings.toml";
+
+/// Problems with the settings file, shown in the settings window.
+#[derive(Resource, Debug, Default)]
+pub struct SettingsStatus {
+    /// Why the saved settings were replaced with the defaults on startup.
+    pub load_warning: Option<String>,
+    /// The last save that failed, cleared by the next one to succeed.
+    pub save_error: Option<String>,
+}
+
+/// Brings settings saved by an older version up to date. Returns true if anything changed.
+pub fn migrate(settings: &mut Settings) -> bool {
+    let from = settings.version;
+    while settings.version < SETTINGS_VERSION {
+        // Files from before versioning have no bindings for the actions added since
+        if settings.version == 0 {
+            let defaults = default_input_map();
+            let unbound: Vec<_> = PlayerMovement::iter().filter(|action| settings.controls.get(action).is_none()).collect();
+            for action in unbound {
+                for input in defaults.get(&action).into_iter().flatten() {
+                    settings.controls.insert(action, input.clone());
+                }
+            }
+        }
+        settings.version += 1;
+    }
+    settings.version != from
+}
+
+/// Loads the settings from `storage`, writing the defaults there on first run. Never panics over the
+/// file itself: anything wrong with it ends up in the returned status instead.
+pub fn load_settings(storage: Storage) -> (Persistent<Settings>, SettingsStatus) {
+    let mut status = SettingsStatus::default();
+    let mut migrated = None;
+    if storage.occupied() {
+        match storage.read::<Settings>(SETTINGS_NAME, StorageFormat::Toml) {
+            Ok(mut settings) => {
+                if migrate(&mut settings) {
+                    info!("migrated settings to version {}", settings.version);
+                    migrated = Some(settings);
+                }
+            }
+            Err(error) => {
+                status.load_warning = Some(match back_up(&storage) {
+                    Some(backup) => format!("Your settings couldn't be read ({error}), so the defaults are in use. The old file was kept at {backup}."),
+                    None => format!("Your settings couldn't be read ({error}), so the defaults are in use."),
+                });
+            }
+        }
+    }
+    // Reverting on errors replaces a bad file with the defaults, now that it's been backed up
+    let mut settings = match Persistent::new(SETTINGS_NAME, StorageFormat::Toml, storage.clone(), true, Settings::default(), true, true) {
+        Ok(settings) => settings,
+        Err(error) => {
+            status.save_error = Some(format!("Settings can't be saved to {storage} ({error}), so changes will be lost on exit."));
+            Persistent::new(SETTINGS_NAME, StorageFormat::Toml, temporary_storage(SETTINGS_FILE), true, Settings::default(), true, true)
+                .expect("failed to initialize settings in a temporary location")
+        }
+    };
+    if let Some(migrated) = migrated {
+        if let Err(error) = settings.set(migrated) {
+            status.save_error = Some(format!("Migrated settings couldn't be saved ({error})."));
+        }
+    }
+    (settings, status)
+}
+
+/// Saves the settings, keeping any error for the settings window rather than panicking.
+pub fn save_settings(settings: &Persistent<Settings>, status: &mut SettingsStatus) {
+    status.save_error = settings.persist().err().map(|error| format!("Settings couldn't be saved ({error})."));
+}
+
+/// Problems with the settings file, for the top of the settings window.
+pub fn settings_status_ui(ui: &mut egui::Ui, status: &mut SettingsStatus) {
+    if let Some(warning) = &status.load_warning {
+        ui.colored_label(egui::Color32::YELLOW, warning);
+        if ui.button("Dismiss").clicked() {
+            status.load_warning = None;
+        }
+    }
+    if let Some(error) = &status.save_error {
+        ui.colored_label(egui::Color32::RED, error);
+    }
+}
+
+/// Loads the settings once, when the app is built.
+pub struct SettingsPlugin {
+    pub storage: Storage,
+}
+
+impl Default for SettingsPlugin {
+    fn default() -> Self {
+        Self { storage: config_storage(SETTINGS_FILE) }
+    }
+}
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let (settings, status) = load_settings(self.storage.clone());
+        app.insert_resource(settings).insert_resource(status);
+    }
+}
@@ -0,0 +1,99 @@
+//! The game's simulation: the clock, the player's needs and money, and whether they're at home, asleep
+//! or at work. Nothing here draws anything or reads input, so it runs under `MinimalPlugins`.
+//!
+//! Menus, key bindings and tests all move the game along by sending [`GameCommand`]s.
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use iyes_progress::prelude::*;
+
+use crate::states::{MyAppState, MyGameState};
+use crate::{DayTimer, GameRng, TIME_FACTOR};
+
+/// Systems that advance the simulation, which anything showing it should run after.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimulationSet;
+
+/// Something the player asked for. Commands that don't fit where the game is, like sleeping at work, are ignored.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameCommand {
+    /// Leaves the main menu for the loading screen.
+    StartGame,
+    GoToWork,
+    GoHome,
+    GoToSleep,
+    Pause,
+    Resume,
+}
+
+fn apply_game_commands(mut game_commands: EventReader<GameCommand>,
+                       app_state: Res<State<MyAppState>>,
+                       game_state: Res<State<MyGameState>>,
+                       mut next_app_state: ResMut<NextState<MyAppState>>,
+                       mut next_game_state: ResMut<NextState<MyGameState>>) {
+    for command in game_commands.read() {
+        match (command, app_state.get(), game_state.get()) {
+            (GameCommand::StartGame, MyAppState::MainMenu, _) => next_app_state.set(MyAppState::LoadingScreen),
+            (GameCommand::GoToWork, MyAppState::InGame, MyGameState::Indoors) => next_game_state.set(MyGameState::Outdoors),
+            (GameCommand::GoHome, MyAppState::InGame, MyGameState::Outdoors) => next_game_state.set(MyGameState::Indoors),
+            (GameCommand::GoToSleep, MyAppState::InGame, MyGameState::Indoors) => next_game_state.set(MyGameState::Sleeping),
+            (GameCommand::Pause, MyAppState::InGame, _) => next_app_state.set(MyAppState::Paused),
+            (GameCommand::Resume, MyAppState::Paused, _) => next_app_state.set(MyAppState::InGame),
+            (command, app_state, game_state) => debug!("ignored {command:?} in {app_state:?} and {game_state:?}"),
+        }
+    }
+}
+
+/// Runs the clock at the day's time factor.
+fn tick_clock(time: Res<Time>, mut day_timer: ResMut<DayTimer>) {
+    let time_factor = day_timer.timefactor;
+    day_timer.stopwatch.tick(time.delta() / time_factor);
+    day_timer.sleepwatch.tick(time.delta() / time_factor);
+}
+
+/// Time flies while the player sleeps.
+fn fall_asleep(mut day_timer: ResMut<DayTimer>) {
+    day_timer.timefactor /= TIME_FACTOR;
+    day_timer.sleepwatch.reset();
+}
+
+fn sleep(time: Res<Time>,
+         mut day_timer: ResMut<DayTimer>,
+         mut next_state: ResMut<NextState<MyGameState>>) {
+    day_timer.sleepwatch.tick(time.delta());
+    if (day_timer.sleepwatch.elapsed_secs() / PI) as u16 > 1 {
+        next_state.set(MyGameState::Indoors);
+    }
+}
+
+fn wake_up(mut day_timer: ResMut<DayTimer>) {
+    day_timer.sleepwatch.reset();
+    day_timer.timefactor *= TIME_FACTOR;
+}
+
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<DayTimer>()
+            .init_resource::<GameRng>()
+            .insert_state(MyAppState::MainMenu)
+            .insert_state(MyGameState::Indoors)
+            .add_event::<GameCommand>()
+            // Whatever has to load reports its progress to this, and the game starts once it all has
+            .add_plugins(ProgressPlugin::new(MyAppState::LoadingScreen).continue_to(MyAppState::InGame))
+            // Commands sent during the frame apply at the start of the next, like a direct state change would
+            .add_systems(PostUpdate, apply_game_commands)
+            .add_systems(
+                Update,
+                (tick_clock, sleep.run_if(in_state(MyGameState::Sleeping)))
+                    .chain()
+                    .in_set(SimulationSet)
+                    .run_if(in_state(MyAppState::InGame)),
+            )
+            .add_systems(OnEnter(MyGameState::Sleeping), fall_asleep)
+            .add_systems(OnExit(MyGameState::Sleeping), wake_up);
+    }
+}
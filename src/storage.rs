@@ -0,0 +1,68 @@
+//! Where settings and saved games are kept: files under the user's config and data directories natively,
+//! and the browser's local storage on the web, where there are no directories to speak of.
+
+use bevy_persistent::storage::Storage;
+
+/// Storage for `file` among the user's settings.
+pub fn config_storage(file: &str) -> Storage {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        let config_dir = dirs::config_dir()
+            .map(|native_config_dir| native_config_dir.join("dracula"))
+            .unwrap_or(std::path::Path::new("local").join("configuration"));
+        Storage::Filesystem { path: config_dir.join(file) }
+    }
+    #[cfg(target_family = "wasm")]
+    {
+        Storage::LocalStorage { key: format!("configuration/{file}") }
+    }
+}
+
+/// Storage for `file` among the user's saved games.
+pub fn data_storage(file: &str) -> Storage {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        let data_dir = dirs::data_dir()
+            .map(|native_data_dir| native_data_dir.join("dracula"))
+            .unwrap_or(std::path::Path::new("local").join("data"));
+        Storage::Filesystem { path: data_dir.join(file) }
+    }
+    #[cfg(target_family = "wasm")]
+    {
+        Storage::LocalStorage { key: format!("data/{file}") }
+    }
+}
+
+/// Storage that only lasts as long as the game does, for when the real one can't be written.
+pub fn temporary_storage(file: &str) -> Storage {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        Storage::Filesystem { path: std::env::temp_dir().join(file) }
+    }
+    #[cfg(target_family = "wasm")]
+    {
+        Storage::SessionStorage { key: file.to_owned() }
+    }
+}
+
+/// Keeps a copy of whatever is in `storage` alongside it, returning where the copy went.
+pub fn back_up(storage: &Storage) -> Option<String> {
+    match storage {
+        #[cfg(not(target_family = "wasm"))]
+        Storage::Filesystem { path } => {
+            let mut backup = path.clone().into_os_string();
+            backup.push(".bak");
+            std::fs::copy(path, &backup).ok()?;
+            Some(std::path::Path::new(&backup).display().to_string())
+        }
+        #[cfg(target_family = "wasm")]
+        Storage::LocalStorage { key } => {
+            let local_storage = web_sys::window()?.local_storage().ok()??;
+            let backup = format!("{key}.bak");
+            local_storage.set_item(&backup, &local_storage.get_item(key).ok()??).ok()?;
+            Some(format!("{backup} in the browser's local storage"))
+        }
+        #[cfg(target_family = "wasm")]
+        Storage::SessionStorage { .. } => None,
+    }
+}
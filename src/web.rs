@@ -0,0 +1,66 @@
+//! Glue between the game and the page hosting it in the browser.
+//!
+//! Browsers only grant pointer lock in answer to a click, which has been handled by the time the app
+//! sees it, so the game marks the canvas with `data-pointer-lock="wanted"` and the loader in
+//! `index.html` asks for the lock on the next click.
+
+use bevy::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::simulation::GameCommand;
+use crate::states::{AppCursorState, MyAppState};
+use crate::CANVAS_SELECTOR;
+
+fn document() -> Option<web_sys::Document> {
+    web_sys::window()?.document()
+}
+
+fn canvas() -> Option<web_sys::HtmlElement> {
+    document()?.query_selector(CANVAS_SELECTOR).ok()??.dyn_into().ok()
+}
+
+fn pointer_locked() -> bool {
+    document().and_then(|document| document.pointer_lock_element()).is_some()
+}
+
+fn request_pointer_lock(cursor_state: Res<State<AppCursorState>>) {
+    let wanted = *cursor_state.get() == AppCursorState::Locked;
+    if let Some(canvas) = canvas() {
+        if wanted {
+            canvas.dataset().set("pointerLock", "wanted").ok();
+        } else {
+            canvas.dataset().delete("pointerLock");
+        }
+    }
+    if !wanted && pointer_locked() {
+        if let Some(document) = document() {
+            document.exit_pointer_lock();
+        }
+    }
+}
+
+/// The browser drops pointer lock on Escape without passing the key on, so losing it pauses the game
+/// the same as the Pause action.
+fn pause_on_lost_pointer_lock(mut was_locked: Local<bool>,
+                              cursor_state: Res<State<AppCursorState>>,
+                              mut game_commands: EventWriter<GameCommand>) {
+    let locked = pointer_locked();
+    if *was_locked && !locked && *cursor_state.get() == AppCursorState::Locked {
+        game_commands.send(GameCommand::Pause);
+    }
+    *was_locked = locked;
+}
+
+pub struct WebPlugin;
+
+impl Plugin for WebPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                request_pointer_lock.run_if(state_changed::<AppCursorState>),
+                pause_on_lost_pointer_lock.run_if(in_state(MyAppState::InGame)),
+            ),
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+use bevy_egui::egui;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use dracula_game::building::{generate_building, BuildingChunk, BuildingChunkData, BuildingIterationParameters, DoorEnum, RoomKind, WALL_THICKNESS};
+use dracula_game::BuildingLayout;
+
+fn rooms(building: &BuildingChunk) -> Vec<&BuildingChunk> {
+    building.get_all().into_iter()
+        .filter(|chunk| !matches!(chunk.divided_chunks, Some(BuildingChunkData::Parent(..))))
+        .collect()
+}
+
+/// Every door as the room it's in and the room it leads to, `None` being outside.
+fn door_graph(rooms: &[&BuildingChunk]) -> Vec<(usize, Option<usize>, DoorEnum)> {
+    let mut doors = Vec::new();
+    for (index, room) in rooms.iter().enumerate() {
+        for (dir, _, door, transform) in room.door_transforms() {
+            let normal = Vec2::from_angle(dir as f32 * std::f32::consts::FRAC_PI_2);
+            let beyond = transform.translation.xz() + normal * WALL_THICKNESS * 1.5;
+            let leads_to = rooms.iter().position(|other| other.level == room.level && other.rect.contains(egui::pos2(beyond.x, beyond.y)));
+            doors.push((index, leads_to, door));
+        }
+    }
+    doors
+}
+
+fn network(networks: &mut Vec<usize>, room: usize) -> usize {
+    if networks[room] != room {
+        let root = network(networks, networks[room]);
+        networks[room] = root;
+    }
+    networks[room]
+}
+
+#[test]
+fn every_room_reaches_the_front_door() {
+    for seed in 0..20 {
+        let house = BuildingLayout::House.generate(&mut StdRng::seed_from_u64(seed)).unwrap();
+        let rooms = rooms(&house);
+        let doors = door_graph(&rooms);
+        let outside = rooms.len();
+        let mut networks: Vec<usize> = (0..=outside).collect();
+        let mut pairs = Vec::new();
+        for &(from, to, door) in &doors {
+            match door {
+                DoorEnum::Exterior => assert_eq!(to, None, "seed {seed}: front door of room {from} opens into a room"),
+                _ => {
+                    let to = to.unwrap_or_else(|| panic!("seed {seed}: {door:?} door of room {from} leads nowhere"));
+                    let pair = (from.min(to), from.max(to));
+                    assert!(!pairs.contains(&pair), "seed {seed}: rooms {pair:?} have more than one door between them");
+                    pairs.push(pair);
+                }
+            }
+            let (from_root, to_root) = (network(&mut networks, from), network(&mut networks, to.unwrap_or(outside)));
+            networks[from_root] = to_root;
+        }
+        assert_eq!(doors.iter().filter(|(.., door)| matches!(door, DoorEnum::Exterior)).count(), 1, "seed {seed}");
+        let front = network(&mut networks, outside);
+        for room in 0..outside {
+            assert_eq!(network(&mut networks, room), front, "seed {seed}: room {room} can't reach the front door");
+        }
+    }
+}
+
+#[test]
+fn rooms_meeting_off_centre_are_joined() {
+    // Neither room's midpoint faces the other, they only share the stretch from x = 6 to 10
+    let room = |min: (f32, f32), max: (f32, f32)| BuildingChunk::new(egui::Rect::from_min_max(min.into(), max.into()), Default::default(), false);
+    let mut building = room((0.0, 0.0), (16.0, 20.0));
+    building.divided_chunks = Some(BuildingChunkData::Parent(vec![room((0.0, 0.0), (10.0, 10.0)), room((6.0, 10.0), (16.0, 20.0))], false));
+    building.connect_rooms();
+
+    let rooms = rooms(&building);
+    let doors = door_graph(&rooms);
+    assert!(doors.iter().any(|&(from, to, _)| to == Some(1 - from)), "{doors:?}");
+    assert_eq!(doors.iter().filter(|(.., door)| matches!(door, DoorEnum::Exterior)).count(), 1, "{doors:?}");
+}
+
+#[test]
+fn main_hallway_is_as_wide_as_asked() {
+    let parameters = BuildingIterationParameters {
+        min_rooms_in_split: 2,
+        max_rooms_in_split: 2,
+        is_hallway: true,
+        hallway_width: 3.0,
+        hallway_branching: 0.0,
+        aspect_ratio_probability_factor: 0.0,
+        aspect_ratio_probability_offset: 1.0,
+        room_requirements: Vec::new(),
+    };
+    let building = generate_building(vec![(parameters, 0)], &mut StdRng::seed_from_u64(1));
+    let hallways: Vec<&BuildingChunk> = rooms(&building).into_iter().filter(|room| room.kind == RoomKind::Hallway).collect();
+    assert_eq!(hallways.len(), 1);
+    assert!((hallways[0].rect.width() - 3.0).abs() < 0.001, "main hallway is {} wide", hallways[0].rect.width());
+}
@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+use bevy_egui::egui;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use dracula_game::building::{BuildingChunk, BuildingChunkData, DoorEnum, RoomKind, STOREY_HEIGHT, WALL_THICKNESS};
+use dracula_game::castle::{generate_castle, CastleParameters};
+use dracula_game::DOOR_WIDTH;
+
+/// A stretch of wall two rooms on the same level share, running along z when `along_z`.
+struct SharedWall {
+    rooms: (usize, usize),
+    along_z: bool,
+    at: f32,
+    from: f32,
+    to: f32,
+}
+
+impl SharedWall {
+    fn between(rooms: (usize, usize), a: egui::Rect, b: egui::Rect) -> Option<Self> {
+        // Rooms laid out side by side only line up to within rounding
+        let touching = |gap: f32| (-0.001..=WALL_THICKNESS).contains(&gap);
+        let (along_z, at, (from, to)) = if touching(b.min.x - a.max.x) || touching(a.min.x - b.max.x) {
+            let at = if touching(b.min.x - a.max.x) { a.max.x } else { a.min.x };
+            (true, at, (a.min.y.max(b.min.y), a.max.y.min(b.max.y)))
+        } else if touching(b.min.y - a.max.y) || touching(a.min.y - b.max.y) {
+            let at = if touching(b.min.y - a.max.y) { a.max.y } else { a.min.y };
+            (false, at, (a.min.x.max(b.min.x), a.max.x.min(b.max.x)))
+        } else {
+            return None;
+        };
+        // Rooms meeting at a corner, or too little of a wall to fit a door in
+        (to - from >= DOOR_WIDTH).then_some(Self { rooms, along_z, at, from, to })
+    }
+
+    fn has(&self, opening: Vec3) -> bool {
+        let (across, along) = if self.along_z { (opening.x, opening.z) } else { (opening.z, opening.x) };
+        (across - self.at).abs() <= WALL_THICKNESS && (self.from..=self.to).contains(&along)
+    }
+}
+
+fn rooms(castle: &BuildingChunk) -> Vec<&BuildingChunk> {
+    castle.get_all().into_iter()
+        .filter(|chunk| !matches!(chunk.divided_chunks, Some(BuildingChunkData::Parent(..))))
+        .collect()
+}
+
+fn shared_walls(rooms: &[&BuildingChunk]) -> Vec<SharedWall> {
+    let mut walls = Vec::new();
+    for a in 0..rooms.len() {
+        for b in a + 1..rooms.len() {
+            if rooms[a].level == rooms[b].level {
+                walls.extend(SharedWall::between((a, b), rooms[a].rect, rooms[b].rect));
+            }
+        }
+    }
+    walls
+}
+
+/// Openings cut on the same storey as `room`.
+fn openings_level_with<'a>(openings: &'a [Vec3], room: &BuildingChunk) -> impl Iterator<Item = Vec3> + 'a {
+    let middle = room.floor_height() + STOREY_HEIGHT / 2.0;
+    openings.iter().copied().filter(move |opening| (opening.y - middle).abs() < 0.01)
+}
+
+fn network(networks: &mut Vec<usize>, room: usize) -> usize {
+    if networks[room] != room {
+        let root = network(networks, networks[room]);
+        networks[room] = root;
+    }
+    networks[room]
+}
+
+#[test]
+fn each_shared_wall_has_one_opening() {
+    for seed in 0..20 {
+        let castle = generate_castle(&CastleParameters::default(), &mut StdRng::seed_from_u64(seed));
+        let rooms = rooms(&castle);
+        let openings = castle.door_openings();
+        let walls = shared_walls(&rooms);
+        for wall in &walls {
+            let count = openings_level_with(&openings, rooms[wall.rooms.0]).filter(|opening| wall.has(*opening)).count();
+            assert_eq!(count, 1, "seed {seed}: rooms {:?} share a wall with {count} openings", wall.rooms);
+        }
+        // The only other opening is the gate
+        assert_eq!(openings.len(), walls.len() + 1, "seed {seed}");
+    }
+}
+
+#[test]
+fn everywhere_is_reachable_from_the_gate() {
+    for seed in 0..20 {
+        let castle = generate_castle(&CastleParameters::default(), &mut StdRng::seed_from_u64(seed));
+        let rooms = rooms(&castle);
+        let openings = castle.door_openings();
+        let outside = rooms.len();
+        let mut networks: Vec<usize> = (0..=outside).collect();
+        let mut join = |a: usize, b: usize| {
+            let (a, b) = (network(&mut networks, a), network(&mut networks, b));
+            networks[a] = b;
+        };
+        for wall in shared_walls(&rooms) {
+            if openings_level_with(&openings, rooms[wall.rooms.0]).any(|opening| wall.has(opening)) {
+                join(wall.rooms.0, wall.rooms.1);
+            }
+        }
+        let mut gates = 0;
+        for (index, room) in rooms.iter().enumerate() {
+            for (.., door, _) in room.door_transforms() {
+                match door {
+                    DoorEnum::Exterior => {
+                        assert_eq!(room.kind, RoomKind::Gallery, "seed {seed}: the gate isn't in a gallery");
+                        gates += 1;
+                        join(index, outside);
+                    }
+                    // Stairs lead to the room right above or below
+                    DoorEnum::Stairs(level) => {
+                        let other = rooms.iter().position(|other| other.level == level && other.rect == room.rect)
+                            .unwrap_or_else(|| panic!("seed {seed}: stairs in room {index} lead nowhere"));
+                        join(index, other);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        assert_eq!(gates, 1, "seed {seed}");
+
+        let gate = network(&mut networks, outside);
+        for (index, room) in rooms.iter().enumerate() {
+            assert_eq!(network(&mut networks, index), gate, "seed {seed}: {:?} room {index} can't be reached from the gate", room.kind);
+        }
+        for kind in [RoomKind::Room, RoomKind::Crypt, RoomKind::Gallery, RoomKind::Tower] {
+            assert!(rooms.iter().any(|room| room.kind == kind), "seed {seed}: no {kind:?}");
+        }
+    }
+}
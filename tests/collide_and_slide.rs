@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::scene::ScenePlugin;
+use bevy::time::TimeUpdateStrategy;
+use bevy_xpbd_3d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use dracula_game::collide_and_slide::{collide_and_slide, step_up, SlideHit, SKIN_WIDTH};
+use dracula_game::{CharacterControllerBundle, CharacterMovementPlugin, GameLayer};
+
+const RADIUS: f32 = 0.4;
+/// Half the height of the upright test capsule, from its centre to the bottom of its lower cap.
+const HALF_HEIGHT: f32 = 0.9;
+const WALL_X: f32 = 2.0;
+/// Same thickness as generated building walls.
+const WALL_THICKNESS: f32 = 0.1;
+
+/// An axis aligned box the test capsule can be swept against.
+struct Slab {
+    min: Vec3,
+    max: Vec3,
+}
+
+impl Slab {
+    /// Sweeps an upright capsule, treated as its bounding box, against the slab.
+    fn cast(&self, origin: Vec3, direction: Direction3d, max_distance: f32) -> Option<SlideHit> {
+        let extents = Vec3::new(RADIUS, HALF_HEIGHT, RADIUS);
+        let min = self.min - extents;
+        let max = self.max + extents;
+        let mut entry = f32::MIN;
+        let mut exit = f32::MAX;
+        let mut normal = Vec3::ZERO;
+        for axis in 0..3 {
+            let (origin, direction, min, max) = (origin[axis], direction[axis], min[axis], max[axis]);
+            if direction.abs() < f32::EPSILON {
+                if origin <= min || origin >= max {
+                    return None;
+                }
+                continue;
+            }
+            let (near, far) = ((min - origin) / direction, (max - origin) / direction);
+            let (near, far) = (near.min(far), near.max(far));
+            if near > entry {
+                entry = near;
+                normal = Vec3::ZERO;
+                normal[axis] = -direction.signum();
+            }
+            exit = exit.min(far);
+        }
+        (entry <= exit && entry >= 0.0 && entry <= max_distance).then_some(SlideHit { distance: entry, normal })
+    }
+}
+
+fn wall() -> Slab {
+    Slab { min: Vec3::new(WALL_X, -10.0, -10.0), max: Vec3::new(WALL_X + WALL_THICKNESS, 10.0, 10.0) }
+}
+
+fn cast_all(slabs: &[Slab]) -> impl FnMut(Vec3, Direction3d, f32) -> Option<SlideHit> + '_ {
+    move |origin, direction, max_distance| {
+        slabs.iter()
+            .filter_map(|slab| slab.cast(origin, direction, max_distance))
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+}
+
+#[test]
+fn never_passes_through_walls() {
+    let slabs = [wall()];
+    for speed in [1.0, 5.0, 20.0, 100.0, 1000.0] {
+        for delta in [1.0 / 144.0, 1.0 / 60.0, 1.0 / 10.0, 0.5] {
+            let mut position = Vec3::X * (WALL_X - RADIUS - 1.0);
+            for _ in 0..200 {
+                position = collide_and_slide(position, Vec3::X * speed * delta, cast_all(&slabs)).position;
+                assert!(position.x + RADIUS <= WALL_X, "passed through at {speed} units/s and {delta}s frames: {position}");
+            }
+            assert!(WALL_X - (position.x + RADIUS) <= SKIN_WIDTH + 0.001, "stopped short of the wall at {position}");
+        }
+    }
+}
+
+#[test]
+fn slides_along_walls() {
+    let slabs = [wall()];
+    let start = Vec3::new(WALL_X - RADIUS - 0.5, 0.0, 0.0);
+    let result = collide_and_slide(start, Vec3::new(2.0, 0.0, 2.0), cast_all(&slabs));
+    assert!(result.position.x + RADIUS <= WALL_X);
+    assert!((result.position.z - 2.0).abs() < 0.001, "lost motion along the wall: {}", result.position);
+    assert_eq!(result.normals, vec![Vec3::NEG_X]);
+    assert_eq!(result.clip_velocity(Vec3::new(5.0, 0.0, 5.0)), Vec3::new(0.0, 0.0, 5.0));
+}
+
+#[test]
+fn stays_inside_corners() {
+    let slabs = [wall(), Slab { min: Vec3::new(-10.0, -10.0, 1.0), max: Vec3::new(10.0, 10.0, 1.0 + WALL_THICKNESS) }];
+    let mut position = Vec3::ZERO;
+    for _ in 0..100 {
+        position = collide_and_slide(position, Vec3::new(3.0, 0.0, 2.0), cast_all(&slabs)).position;
+        assert!(position.x + RADIUS <= WALL_X && position.z + RADIUS <= 1.0, "escaped the corner at {position}");
+    }
+}
+
+#[test]
+fn steps_onto_low_ledges_only() {
+    let floor = Slab { min: Vec3::new(-10.0, -1.0, -10.0), max: Vec3::new(10.0, 0.0, 10.0) };
+    let ledge = |height: f32| Slab { min: Vec3::new(1.0, 0.0, -10.0), max: Vec3::new(10.0, height, 10.0) };
+    let start = Vec3::new(0.0, HALF_HEIGHT + SKIN_WIDTH, 0.0);
+    let motion = Vec3::X * 1.0;
+
+    let slabs = [floor, ledge(0.2)];
+    let slid = collide_and_slide(start, motion, cast_all(&slabs));
+    let stepped = step_up(start, motion, &slid, 0.25, std::f32::consts::FRAC_PI_4, cast_all(&slabs)).expect("didn't step onto a low ledge");
+    assert!(stepped.position.x > slid.position.x);
+    assert!(stepped.position.y - HALF_HEIGHT >= 0.2);
+
+    let slabs = [Slab { min: Vec3::new(-10.0, -1.0, -10.0), max: Vec3::new(10.0, 0.0, 10.0) }, ledge(0.5)];
+    let slid = collide_and_slide(start, motion, cast_all(&slabs));
+    assert!(step_up(start, motion, &slid, 0.25, std::f32::consts::FRAC_PI_4, cast_all(&slabs)).is_none());
+}
+
+/// The controller's own systems and real physics, with a building wall for it to run into.
+fn physics_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        TransformPlugin,
+        HierarchyPlugin,
+        InputPlugin,
+        AssetPlugin::default(),
+        ScenePlugin,
+        PhysicsPlugins::default(),
+        CharacterMovementPlugin,
+    ))
+        .init_asset::<Mesh>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(1.0 / 60.0)));
+    app.world.spawn((
+        TransformBundle::from_transform(Transform::from_xyz(WALL_X + WALL_THICKNESS / 2.0, 0.0, 0.0)),
+        RigidBody::Static,
+        Collider::cuboid(WALL_THICKNESS, 20.0, 20.0),
+        CollisionLayers::new(GameLayer::Environment, [GameLayer::Player]),
+    ));
+    app
+}
+
+#[test]
+fn physics_never_lets_controllers_through_walls() {
+    for speed in [5.0, 50.0, 500.0, 5000.0] {
+        let mut app = physics_app();
+        let controller = app.world.spawn((
+            TransformBundle::from_transform(Transform::from_xyz(WALL_X - RADIUS - 1.0, 0.0, 0.0)),
+            CharacterControllerBundle::new(Collider::capsule(2.0 * (HALF_HEIGHT - RADIUS), RADIUS), InputManagerBundle::with_map(InputMap::default())),
+        )).id();
+        // The wall only shows up in spatial queries once a physics step has run
+        app.update();
+        for _ in 0..120 {
+            app.world.get_mut::<LinearVelocity>(controller).unwrap().0 = Vec3::X * speed;
+            app.update();
+            let position = app.world.get::<Position>(controller).unwrap().0;
+            assert!(position.x + RADIUS <= WALL_X + 0.001, "passed through at {speed} units/s: {position}");
+        }
+        let position = app.world.get::<Position>(controller).unwrap().0;
+        assert!(WALL_X - (position.x + RADIUS) <= SKIN_WIDTH + 0.01, "stopped short of the wall at {speed} units/s: {position}");
+    }
+}
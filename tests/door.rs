@@ -0,0 +1,39 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use dracula_game::building::DoorEnum;
+use dracula_game::door::{DoorHinge, DoorState, DOOR_OPEN_ACTION};
+use dracula_game::interactable::InteractableAction;
+use dracula_game::states::MyAppState;
+use dracula_game::{headless_app, BuildingLayout};
+
+#[test]
+fn stairs_have_no_door_to_open() {
+    let seed = 4;
+    let castle = BuildingLayout::Castle.generate(&mut StdRng::seed_from_u64(seed)).unwrap();
+    let stairs = castle.get_all().into_iter()
+        .flat_map(|chunk| chunk.door_transforms())
+        .filter(|(.., door, _)| matches!(door, DoorEnum::Stairs(_)))
+        .count();
+    assert!(stairs > 0);
+
+    let mut app = headless_app(seed);
+    app.insert_resource(BuildingLayout::Castle);
+    for _ in 0..100 {
+        if *app.world.resource::<State<MyAppState>>().get() == MyAppState::InGame {
+            break;
+        }
+        app.update();
+    }
+    let unhinged: Vec<Entity> = app.world.query_filtered::<Entity, (With<DoorState>, Without<DoorHinge>)>().iter(&app.world).collect();
+    assert_eq!(unhinged.len(), stairs);
+
+    for &entity in &unhinged {
+        app.world.send_event(InteractableAction { entity, action: DOOR_OPEN_ACTION });
+    }
+    app.update();
+    for entity in unhinged {
+        assert_eq!(*app.world.get::<DoorState>(entity).unwrap(), DoorState::Closed);
+    }
+}
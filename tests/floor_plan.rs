@@ -0,0 +1,66 @@
+use bevy::prelude::*;
+
+use dracula_game::building::{BuildingChunkData, DoorEnum};
+use dracula_game::floor_plan::{load_floor_plan, parse_floor_plan, FloorPlanError};
+use dracula_game::BuildingLayout;
+
+const PLAN: &str = "
+scale = 2.0
+a = Hall
+b = Study
+
+#######
+#aaaDb#
+#aaa#b#
+#E#####
+";
+
+#[test]
+fn each_marked_door_is_cut_once_where_it_is_drawn() {
+    let building = parse_floor_plan(PLAN).unwrap();
+    let openings = building.door_openings();
+    assert_eq!(openings.len(), 2, "{openings:?}");
+    // Cells are `scale` wide, so the door in column 4, row 1 is centred at (9, 3)
+    assert!(openings.iter().any(|opening| opening.xz() == Vec2::new(9.0, 3.0)), "{openings:?}");
+    assert!(openings.iter().any(|opening| opening.xz() == Vec2::new(3.0, 7.0)), "{openings:?}");
+
+    let Some(BuildingChunkData::Parent(rooms, _)) = &building.divided_chunks else {
+        panic!("floor plan has no rooms");
+    };
+    // The door between the rooms belongs to the hall, which comes first
+    assert_eq!(rooms[0].doors.iter().map(Vec::len).sum::<usize>(), 2);
+    assert_eq!(rooms[1].doors.iter().map(Vec::len).sum::<usize>(), 0);
+}
+
+#[test]
+fn authored_plans_have_one_opening_per_door_cell() {
+    for name in ["harker_office", "count_study"] {
+        let text = std::fs::read_to_string(format!("assets/floor_plans/{name}.txt")).unwrap();
+        let door_cells = text.lines()
+            .filter(|line| !line.contains('=') && !line.trim_start().starts_with("//"))
+            .flat_map(str::chars)
+            .filter(|cell| matches!(cell, 'E' | 'H' | 'D' | 'L'))
+            .count();
+        let building = load_floor_plan(format!("assets/floor_plans/{name}.txt")).unwrap();
+        assert_eq!(building.door_openings().len(), door_cells, "{name}");
+    }
+}
+
+#[test]
+fn every_shipped_plan_has_a_way_out() {
+    for entry in std::fs::read_dir("assets/floor_plans").unwrap() {
+        let path = entry.unwrap().path();
+        let building = load_floor_plan(&path).unwrap();
+        let exteriors = building.get_all().into_iter()
+            .flat_map(|chunk| chunk.doors.iter().flatten())
+            .filter(|door| matches!(door, DoorEnum::Exterior))
+            .count();
+        assert!(exteriors > 0, "{} has no exterior door", path.display());
+    }
+}
+
+#[test]
+fn missing_plans_are_reported() {
+    let layout = BuildingLayout::FloorPlan("no_such_plan".to_string());
+    assert!(matches!(layout.generate(&mut rand::thread_rng()), Err(FloorPlanError::Io(_))));
+}
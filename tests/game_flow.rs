@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+
+use dracula_game::simulation::{GameCommand, SimulationPlugin};
+use dracula_game::states::{MyAppState, MyGameState};
+
+const FRAME: Duration = Duration::from_millis(100);
+
+/// The game's simulation without a window, driven by the same commands as the menus and key bindings.
+struct Harness {
+    app: App,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, SimulationPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME));
+        let mut harness = Self { app };
+        harness.step(1);
+        harness
+    }
+
+    /// Starts a game and waits for loading to finish.
+    fn in_game() -> Self {
+        let mut harness = Self::new();
+        harness.send(GameCommand::StartGame);
+        harness.step_until("the game to load", |harness| harness.app_state() == MyAppState::InGame);
+        harness
+    }
+
+    fn send(&mut self, command: GameCommand) -> &mut Self {
+        self.app.world.send_event(command);
+        self
+    }
+
+    fn step(&mut self, frames: usize) -> &mut Self {
+        for _ in 0..frames {
+            self.app.update();
+        }
+        self
+    }
+
+    /// Steps until `done` holds, returning how many frames that took.
+    fn step_until(&mut self, what: &str, done: impl Fn(&Self) -> bool) -> usize {
+        for frame in 0..1000 {
+            if done(self) {
+                return frame;
+            }
+            self.app.update();
+        }
+        panic!("gave up waiting for {what}");
+    }
+
+    fn app_state(&self) -> MyAppState {
+        self.app.world.resource::<State<MyAppState>>().get().clone()
+    }
+
+    fn game_state(&self) -> MyGameState {
+        self.app.world.resource::<State<MyGameState>>().get().clone()
+    }
+}
+
+#[test]
+fn start_game_loads_into_the_game() {
+    let mut harness = Harness::new();
+    assert_eq!(harness.app_state(), MyAppState::MainMenu);
+    harness.send(GameCommand::StartGame).step(2);
+    // Nothing is being loaded without the presentation, so loading finishes straight away
+    assert!(matches!(harness.app_state(), MyAppState::LoadingScreen | MyAppState::InGame));
+    harness.step_until("the game to load", |harness| harness.app_state() == MyAppState::InGame);
+    assert_eq!(harness.game_state(), MyGameState::Indoors);
+}
+
+#[test]
+fn sleeping_wakes_up_indoors() {
+    let mut harness = Harness::in_game();
+    harness.send(GameCommand::GoToSleep).step(2);
+    assert_eq!(harness.game_state(), MyGameState::Sleeping);
+    let frames = harness.step_until("the player to wake up", |harness| harness.game_state() == MyGameState::Indoors);
+    assert!(frames > 10, "woke up after only {frames} frames");
+    assert_eq!(harness.app_state(), MyAppState::InGame);
+}
+
+#[test]
+fn pausing_and_resuming_returns_to_the_game() {
+    let mut harness = Harness::in_game();
+    harness.send(GameCommand::GoToWork).step(2);
+    harness.send(GameCommand::Pause).step(2);
+    assert_eq!(harness.app_state(), MyAppState::Paused);
+    // Nothing happens while paused
+    harness.send(GameCommand::GoHome).step(2);
+    assert_eq!(harness.game_state(), MyGameState::Outdoors);
+    harness.send(GameCommand::Resume).step(2);
+    assert_eq!(harness.app_state(), MyAppState::InGame);
+    assert_eq!(harness.game_state(), MyGameState::Outdoors);
+}
+
+#[test]
+fn commands_out_of_place_are_ignored() {
+    let mut harness = Harness::new();
+    harness.send(GameCommand::GoToSleep).send(GameCommand::Resume).step(2);
+    assert_eq!(harness.app_state(), MyAppState::MainMenu);
+    assert_eq!(harness.game_state(), MyGameState::Indoors);
+
+    let mut harness = Harness::in_game();
+    harness.send(GameCommand::GoToWork).step(2);
+    harness.send(GameCommand::GoToSleep).send(GameCommand::StartGame).step(2);
+    assert_eq!(harness.app_state(), MyAppState::InGame);
+    assert_eq!(harness.game_state(), MyGameState::Outdoors);
+}
@@ -0,0 +1,88 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde_json::Value;
+
+use dracula_game::building::{BuildingChunk, BuildingChunkData};
+use dracula_game::gltf_export::export_building;
+use dracula_game::BuildingLayout;
+
+fn u32_at(bytes: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize
+}
+
+/// Writes `building` out as `.glb` and reads back its json and binary chunk, checking the header on the way.
+fn export_glb(building: &BuildingChunk, name: &str) -> (Value, Vec<u8>) {
+    let path = std::env::temp_dir().join(format!("dracula_gltf_export_{name}.glb"));
+    export_building(building, &path).unwrap();
+    let glb = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+
+    assert_eq!(&glb[0..4], b"glTF");
+    assert_eq!(u32_at(&glb, 4), 2);
+    assert_eq!(u32_at(&glb, 8), glb.len(), "header length doesn't match the file");
+    let json_length = u32_at(&glb, 12);
+    assert_eq!(&glb[16..20], b"JSON");
+    assert_eq!(json_length % 4, 0);
+    let json = serde_json::from_slice(&glb[20..20 + json_length]).expect("json chunk doesn't parse");
+    let binary_start = 20 + json_length;
+    let binary_length = u32_at(&glb, binary_start);
+    assert_eq!(&glb[binary_start + 4..binary_start + 8], b"BIN\0");
+    assert_eq!(binary_length % 4, 0);
+    assert_eq!(binary_start + 8 + binary_length, glb.len());
+    (json, glb[binary_start + 8..].to_vec())
+}
+
+fn check_counts(building: &BuildingChunk, json: &Value, binary: &[u8]) {
+    let rooms = building.get_all().into_iter()
+        .filter(|chunk| !matches!(chunk.divided_chunks, Some(BuildingChunkData::Parent(..))))
+        .count();
+    let nodes = json["nodes"].as_array().unwrap();
+    let meshes = json["meshes"].as_array().unwrap();
+    assert_eq!(nodes.len(), rooms);
+    assert_eq!(meshes.len(), rooms);
+    assert_eq!(json["scenes"][0]["nodes"].as_array().unwrap().len(), rooms);
+
+    // Every primitive has its own positions, normals and indices
+    let primitives: Vec<&Value> = meshes.iter().flat_map(|mesh| mesh["primitives"].as_array().unwrap()).collect();
+    assert!(primitives.len() >= rooms, "some rooms have no geometry");
+    let accessors = json["accessors"].as_array().unwrap();
+    let views = json["bufferViews"].as_array().unwrap();
+    assert_eq!(accessors.len(), 3 * primitives.len());
+    assert_eq!(views.len(), accessors.len());
+    for primitive in &primitives {
+        let position = &accessors[primitive["attributes"]["POSITION"].as_u64().unwrap() as usize];
+        let normal = &accessors[primitive["attributes"]["NORMAL"].as_u64().unwrap() as usize];
+        let indices = &accessors[primitive["indices"].as_u64().unwrap() as usize];
+        assert_eq!(position["count"], normal["count"]);
+        assert_eq!(indices["count"].as_u64().unwrap() % 3, 0);
+    }
+
+    let byte_length = json["buffers"][0]["byteLength"].as_u64().unwrap() as usize;
+    assert!(json["buffers"][0].get("uri").is_none(), "a .glb buffer lives in the file");
+    assert!(byte_length <= binary.len() && binary.len() - byte_length < 4, "buffer is {byte_length} bytes, binary chunk is {}", binary.len());
+    let mut end = 0;
+    for (accessor, view) in accessors.iter().zip(views) {
+        let (offset, length) = (view["byteOffset"].as_u64().unwrap() as usize, view["byteLength"].as_u64().unwrap() as usize);
+        let element = if accessor["type"] == "VEC3" { 12 } else { 4 };
+        assert_eq!(length, accessor["count"].as_u64().unwrap() as usize * element);
+        assert!(offset >= end && offset % 4 == 0, "views overlap or are misaligned");
+        end = offset + length;
+    }
+    assert_eq!(end, byte_length);
+}
+
+#[test]
+fn house_exports_to_glb() {
+    let house = BuildingLayout::House.generate(&mut StdRng::seed_from_u64(5)).unwrap();
+    let (json, binary) = export_glb(&house, "house");
+    check_counts(&house, &json, &binary);
+}
+
+#[test]
+fn floor_plan_exports_to_glb() {
+    let plan = BuildingLayout::FloorPlan("harker_office".to_string()).generate(&mut StdRng::seed_from_u64(5)).unwrap();
+    let (json, binary) = export_glb(&plan, "harker_office");
+    check_counts(&plan, &json, &binary);
+    let names: Vec<&str> = json["nodes"].as_array().unwrap().iter().map(|node| node["name"].as_str().unwrap()).collect();
+    assert!(names.iter().all(|name| !name.starts_with("room_")), "floor plan rooms lost their tags: {names:?}");
+}
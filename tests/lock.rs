@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+use bevy_persistent::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use dracula_game::lock::{DoorLock, KeyPickup, LockSave, FRONT_DOOR_KEY};
+use dracula_game::states::MyAppState;
+use dracula_game::{headless_app, BuildingLayout, PlayerMovement};
+
+fn doors_with_keys(app: &mut App) -> Vec<String> {
+    let locks: Vec<DoorLock> = app.world.query::<&DoorLock>().iter(&app.world).cloned().collect();
+    let lock_save = app.world.resource::<Persistent<LockSave>>();
+    locks.into_iter()
+        .filter(|lock| lock.key_id != FRONT_DOOR_KEY && lock_save.key_for(lock).is_some())
+        .map(|lock| lock.key_id)
+        .collect()
+}
+
+#[test]
+fn picking_up_a_key_opens_only_its_door() {
+    let mut app = headless_app(3);
+    app.insert_resource(BuildingLayout::FloorPlan("harker_office".to_string()));
+    // The headless locks outlive each run, so start without any keys picked up before
+    let mut lock_save = app.world.resource_mut::<Persistent<LockSave>>();
+    lock_save.keys.retain(|key| key.door == FRONT_DOOR_KEY);
+    lock_save.doors.clear();
+    // Once in the game the player has been put at the spawn, and stays where they're put
+    for _ in 0..100 {
+        if *app.world.resource::<State<MyAppState>>().get() == MyAppState::InGame {
+            break;
+        }
+        app.update();
+    }
+    app.update();
+    let (key, at) = {
+        let (key, transform) = app.world.query::<(&KeyPickup, &Transform)>().single(&app.world);
+        (key.0.clone(), transform.translation)
+    };
+    assert_eq!(key.name, "Archive key");
+    assert!(doors_with_keys(&mut app).is_empty());
+    assert!(app.world.resource::<Persistent<LockSave>>().doors.is_empty(), "spawning the doors saved them");
+
+    let mut player = app.world.query_filtered::<&mut Transform, With<ActionState<PlayerMovement>>>().single_mut(&mut app.world);
+    player.translation.x = at.x;
+    player.translation.z = at.z;
+    app.update();
+
+    assert!(app.world.resource::<Persistent<LockSave>>().keys.contains(&key));
+    assert_eq!(app.world.query::<&KeyPickup>().iter(&app.world).count(), 0, "the key is still lying there");
+    // The archive has the only door the key fits
+    assert_eq!(doors_with_keys(&mut app), vec![key.door.clone()]);
+}
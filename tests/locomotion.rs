@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::scene::ScenePlugin;
+use bevy::time::TimeUpdateStrategy;
+use bevy_xpbd_3d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use dracula_game::locomotion::{accelerate, MovementSettings};
+use dracula_game::simulation::SimulationPlugin;
+use dracula_game::states::MyAppState;
+use dracula_game::{CharacterControllerBundle, CharacterMovementPlugin, GameLayer, PlayerMovement};
+
+const TICK_RATES: [f32; 5] = [20.0, 30.0, 60.0, 144.0, 240.0];
+
+/// Holds forward for `walk` seconds then lets go for `coast` seconds, returning the distance covered and the final velocity.
+fn walk_then_coast(tick_rate: f32, walk: f32, coast: f32) -> (f32, Vec3) {
+    let settings = MovementSettings::default();
+    let delta = 1.0 / tick_rate;
+    let mut velocity = Vec3::ZERO;
+    let mut distance = 0.0;
+    for tick in 0..((walk + coast) * tick_rate).round() as usize {
+        let wish = if (tick as f32) < walk * tick_rate { Vec3::X } else { Vec3::ZERO };
+        let next = accelerate(velocity, wish, &settings, 1.0, delta);
+        // Average of both ends of the step, the same as integrating the velocity ramp exactly
+        distance += (velocity.x + next.x) / 2.0 * delta;
+        velocity = next;
+        assert!(velocity.length() <= settings.max_speed + 0.0001, "went over max speed at {tick_rate}Hz: {velocity}");
+    }
+    (distance, velocity)
+}
+
+#[test]
+fn covers_the_same_distance_at_any_tick_rate() {
+    let (reference, _) = walk_then_coast(1000.0, 2.0, 1.0);
+    for tick_rate in TICK_RATES {
+        let (distance, _) = walk_then_coast(tick_rate, 2.0, 1.0);
+        assert!((distance - reference).abs() / reference < 0.01, "covered {distance} at {tick_rate}Hz against {reference}");
+    }
+}
+
+#[test]
+fn reaches_top_speed_at_the_same_time() {
+    let settings = MovementSettings::default();
+    let time_to_top_speed = settings.max_speed / settings.acceleration;
+    for tick_rate in TICK_RATES {
+        let (_, velocity) = walk_then_coast(tick_rate, time_to_top_speed + 1.0 / tick_rate, 0.0);
+        assert!((velocity.x - settings.max_speed).abs() < 0.0001, "only reached {velocity} at {tick_rate}Hz");
+    }
+}
+
+#[test]
+fn friction_is_tick_rate_independent() {
+    let (_, reference) = walk_then_coast(1000.0, 1.0, 0.5);
+    for tick_rate in TICK_RATES {
+        let (_, velocity) = walk_then_coast(tick_rate, 1.0, 0.5);
+        assert!((velocity - reference).length() < 0.001, "slowed to {velocity} at {tick_rate}Hz against {reference}");
+    }
+}
+
+#[test]
+fn leaves_vertical_velocity_alone() {
+    let velocity = accelerate(Vec3::new(0.0, -3.0, 0.0), Vec3::Z, &MovementSettings::default(), 1.0, 1.0 / 60.0);
+    assert_eq!(velocity.y, -3.0);
+    assert!(velocity.z > 0.0);
+}
+
+/// The controller's own systems on a floor, with fixed ticks and frames both at `tick_rate`.
+fn controller_app(tick_rate: f64) -> (App, Entity) {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        TransformPlugin,
+        HierarchyPlugin,
+        InputPlugin,
+        AssetPlugin::default(),
+        ScenePlugin,
+        SimulationPlugin,
+        PhysicsPlugins::default(),
+        CharacterMovementPlugin,
+    ))
+        .init_asset::<Mesh>()
+        .insert_resource(Time::<Fixed>::from_hz(tick_rate))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(1.0 / tick_rate)));
+    app.world.resource_mut::<NextState<MyAppState>>().set(MyAppState::InGame);
+    app.world.spawn((
+        TransformBundle::from_transform(Transform::from_xyz(0.0, -0.5, 0.0)),
+        RigidBody::Static,
+        Collider::cuboid(200.0, 1.0, 200.0),
+        CollisionLayers::new(GameLayer::Environment, [GameLayer::Player]),
+    ));
+    let mut input_map = InputMap::default();
+    input_map.insert(PlayerMovement::Move, VirtualDPad::wasd());
+    let controller = app.world.spawn((
+        TransformBundle::from_transform(Transform::from_xyz(0.0, 0.95, 0.0)),
+        CharacterControllerBundle::new(Collider::capsule(1.0, 0.4), InputManagerBundle::with_map(input_map)),
+    )).id();
+    (app, controller)
+}
+
+/// Walks forward for `walk` seconds and coasts for `coast`, returning where the controller ended up.
+fn walk_then_coast_in_app(tick_rate: f64, walk: f64, coast: f64) -> Vec3 {
+    let (mut app, controller) = controller_app(tick_rate);
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::KeyW);
+    for _ in 0..(walk * tick_rate).round() as usize {
+        app.update();
+    }
+    app.world.resource_mut::<ButtonInput<KeyCode>>().release(KeyCode::KeyW);
+    for _ in 0..(coast * tick_rate).round() as usize {
+        app.update();
+    }
+    app.world.get::<Position>(controller).unwrap().0
+}
+
+#[test]
+fn controller_covers_the_same_ground_at_any_tick_rate() {
+    let reference = walk_then_coast_in_app(60.0, 2.0, 1.0);
+    assert!(reference.xz().length() > 1.0, "barely moved: {reference}");
+    for tick_rate in [30.0, 144.0] {
+        let position = walk_then_coast_in_app(tick_rate, 2.0, 1.0);
+        assert!(position.xz().distance(reference.xz()) / reference.xz().length() < 0.02,
+                "ended up at {position} at {tick_rate}Hz against {reference} at 60Hz");
+    }
+}
@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+use bevy::app::AppExit;
+use bevy::input::mouse::MouseMotion;
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use leafwing_input_manager::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use dracula_game::replay::{InputRecorder, InputRecording, RecordPlugin, Replay, ReplayPlugin};
+use dracula_game::{headless_app, replay_headless, BuildingLayout, GameRng, PlayerMovement};
+
+#[derive(Actionlike, Clone, Copy, PartialEq, Eq, Hash, Debug, Reflect, Serialize, Deserialize)]
+enum Action {
+    Move,
+    Jump,
+}
+
+#[derive(Component, Default)]
+struct Jumps(u32);
+
+/// Stands in for the game's movement, so the outcome depends on both the input and the frame times.
+fn walk(time: Res<Time>, mut q_walker: Query<(&ActionState<Action>, &mut Transform, &mut Jumps)>) {
+    for (action_state, mut transform, mut jumps) in &mut q_walker {
+        if let Some(axis_pair) = action_state.clamped_axis_pair(&Action::Move) {
+            transform.translation += Vec3::new(axis_pair.x(), 0.0, -axis_pair.y()) * time.delta_seconds();
+            transform.rotate_y(axis_pair.x() * time.delta_seconds());
+        }
+        if action_state.just_pressed(&Action::Jump) {
+            jumps.0 += 1;
+        }
+    }
+}
+
+fn app(start: Vec3) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, InputPlugin, InputManagerPlugin::<Action>::default()))
+        .add_systems(Update, walk);
+    let mut input_map = InputMap::default();
+    input_map.insert(Action::Move, VirtualDPad::wasd());
+    input_map.insert(Action::Jump, KeyCode::Space);
+    app.world.spawn((InputManagerBundle::with_map(input_map), Transform::from_translation(start), Jumps::default()));
+    app
+}
+
+fn walker(app: &mut App) -> (Transform, u32) {
+    let (transform, jumps) = app.world.query::<(&Transform, &Jumps)>().single(&app.world);
+    (*transform, jumps.0)
+}
+
+/// Wanders about for a while with uneven frame times, jumping now and then.
+fn record(path: &std::path::Path) -> (Transform, u32) {
+    let mut app = app(Vec3::new(1.0, 2.0, 3.0));
+    app.add_plugins(RecordPlugin::<Action>::new(path));
+    let mut rng = GameRng::new(7);
+    for frame in 0..120 {
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(rng.rng().gen_range(0.005..0.05))));
+        let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+        keys.release_all();
+        if frame % 40 < 25 {
+            keys.press(KeyCode::KeyW);
+        }
+        if frame % 30 > 20 {
+            keys.press(KeyCode::KeyD);
+        }
+        if frame % 17 == 0 {
+            keys.press(KeyCode::Space);
+        }
+        app.update();
+    }
+    let recorded = walker(&mut app);
+    // Saving happens as the app exits, after one more frame is recorded
+    app.world.resource_mut::<ButtonInput<KeyCode>>().release_all();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+    app.world.send_event(AppExit);
+    app.update();
+    assert_eq!(app.world.resource::<InputRecorder<Action>>().recording.as_ref().unwrap().frames.len(), 121);
+    recorded
+}
+
+#[test]
+fn replay_ends_where_the_recording_did() {
+    let path = std::env::temp_dir().join("dracula_replay_ends_where_the_recording_did.ron");
+    let (recorded, recorded_jumps) = record(&path);
+    let recording = InputRecording::<Action>::load(&path).expect("failed to load the recording");
+    assert_eq!(recording.translation, Vec3::new(1.0, 2.0, 3.0));
+    assert!(recorded_jumps > 0);
+
+    // Starts somewhere else, and with nothing pressed, to show both come from the recording
+    let mut app = app(Vec3::ZERO);
+    app.add_plugins(ReplayPlugin::new(recording));
+    for _ in 0..120 {
+        app.update();
+    }
+    let (replayed, replayed_jumps) = walker(&mut app);
+    assert_eq!(replayed.translation, recorded.translation);
+    assert_eq!(replayed.rotation, recorded.rotation);
+    assert_eq!(replayed_jumps, recorded_jumps);
+
+    app.update();
+    assert!(app.world.resource::<Replay<Action>>().finished());
+    assert!(!app.world.resource::<Events<AppExit>>().is_empty(), "didn't exit once the replay finished");
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn replay_reseeds_the_game_rng() {
+    let recording = InputRecording::<Action> { seed: 1234, translation: Vec3::ZERO, rotation: Quat::IDENTITY, frames: Vec::new() };
+    let mut app = app(Vec3::ZERO);
+    app.insert_resource(GameRng::new(1));
+    app.add_plugins(ReplayPlugin::new(recording));
+    assert_eq!(app.world.resource::<GameRng>().seed(), 1234);
+}
+
+#[test]
+fn same_seed_lays_out_the_same_building() {
+    for layout in [BuildingLayout::House, BuildingLayout::Castle] {
+        let rooms = |seed| layout.generate(GameRng::new(seed).rng()).unwrap().get_all().into_iter().map(|chunk| chunk.rect).collect::<Vec<_>>();
+        assert_eq!(rooms(99), rooms(99), "{layout:?} changed with the same seed");
+    }
+}
+
+fn player(app: &mut App) -> Transform {
+    *app.world.query_filtered::<&Transform, With<ActionState<PlayerMovement>>>().single(&app.world)
+}
+
+/// Plays the real game without a window for a while, walking into walls, turning and jumping.
+fn record_headless(path: &std::path::Path) -> (Transform, Transform) {
+    let mut app = headless_app(42);
+    app.add_plugins(RecordPlugin::<PlayerMovement>::new(path));
+    let mut rng = GameRng::new(7);
+    let mut start = None;
+    for frame in 0..300 {
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(rng.rng().gen_range(0.005..0.05))));
+        let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+        keys.release_all();
+        if frame % 60 < 45 {
+            keys.press(KeyCode::KeyW);
+        }
+        if frame % 50 > 35 {
+            keys.press(KeyCode::KeyA);
+        }
+        if frame % 80 > 60 {
+            keys.press(KeyCode::ControlLeft);
+        }
+        if frame % 45 == 0 {
+            keys.press(KeyCode::Space);
+        }
+        if frame % 70 < 10 {
+            app.world.send_event(MouseMotion { delta: Vec2::new(40.0, 0.0) });
+        }
+        app.update();
+        start = start.or(app.world.query_filtered::<&Transform, With<ActionState<PlayerMovement>>>().iter(&app.world).next().copied());
+    }
+    app.world.resource_mut::<ButtonInput<KeyCode>>().release_all();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+    app.world.send_event(AppExit);
+    app.update();
+    (start.expect("the player never spawned"), player(&mut app))
+}
+
+#[test]
+fn headless_replay_moves_the_player_as_recorded() {
+    let path = std::env::temp_dir().join("dracula_headless_replay_moves_the_player_as_recorded.ron");
+    let (start, recorded) = record_headless(&path);
+    assert!(start.translation.xz().distance(recorded.translation.xz()) > 1.0, "the player didn't go anywhere");
+    assert!(start.rotation.angle_between(recorded.rotation) > 0.1, "the player didn't turn");
+
+    let recording = InputRecording::<PlayerMovement>::load(&path).expect("failed to load the recording");
+    assert_eq!(recording.seed, 42);
+    let replayed = replay_headless(recording);
+    assert_eq!(replayed.translation, recorded.translation);
+    assert_eq!(replayed.rotation, recorded.rotation);
+    std::fs::remove_file(path).ok();
+}
@@ -0,0 +1,7 @@
+#!/bin/sh
+# Builds the game for the browser into pkg/, next to index.html.
+# Needs the wasm32-unknown-unknown target and a wasm-bindgen CLI of the same version as the crate in Cargo.lock.
+set -e
+cd "$(dirname "$0")/.."
+cargo build --release --lib --target wasm32-unknown-unknown
+wasm-bindgen --target web --no-typescript --out-dir pkg target/wasm32-unknown-unknown/release/dracula_game.wasm
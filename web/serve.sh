@@ -0,0 +1,4 @@
+#!/bin/sh
+# Serves the web build at http://127.0.0.1:${PORT:-8000}/, with assets/ fetched from next to index.html.
+cd "$(dirname "$0")/.."
+exec python3 -m http.server "${PORT:-8000}" --bind 127.0.0.1
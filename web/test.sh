@@ -0,0 +1,22 @@
+#!/bin/sh
+# Builds the web target, serves it and checks it starts in a headless browser.
+# Set CHROME to the browser binary if it isn't chromium on the PATH, and SKIP_BUILD to test an existing pkg/.
+set -e
+cd "$(dirname "$0")/.."
+[ -n "$SKIP_BUILD" ] || web/build.sh
+PORT="${PORT:-8000}"
+PORT="$PORT" web/serve.sh >/dev/null 2>&1 &
+server=$!
+trap 'kill $server' EXIT
+sleep 1
+
+# Rendering goes through the software WebGL implementation, since there's no GPU to hand
+dom=$("${CHROME:-chromium}" --headless=new --use-angle=swiftshader --enable-unsafe-swiftshader \
+    --virtual-time-budget="${BUDGET_MS:-20000}" --dump-dom "http://127.0.0.1:$PORT/")
+state=$(printf '%s' "$dom" | sed -n 's/.*<body data-state="\([a-z]*\)".*/\1/p')
+if [ "$state" != "running" ]; then
+    echo "the game didn't start (state: ${state:-unknown})"
+    printf '%s\n' "$dom" | sed -n 's/.*<div id="status">\(.*\)<\/div>.*/\1/p'
+    exit 1
+fi
+echo "the game started"